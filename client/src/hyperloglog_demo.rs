@@ -91,7 +91,7 @@ pub fn HyperLogLogDemo() -> Element {
                 button {
                     class: "control-button reset-button",
                     onclick: move |_| {
-                        hll.set(HyperLogLog::new(bits()));
+                        hll.write().clear();
                         real_count.set(0);
                         is_running.set(false);
                         // Reset RNG to initial seed for reproducibility
@@ -357,9 +357,13 @@ fn RepoRunner(repository: String, latest_version: Option<String>) -> Element {
 
                         // Use composite key of r2_key + function_name for HLL tracking
                         let hll_key = format!("{}:{}", executable.r2_key, executable.function_name);
-                        let hll = local_hll
-                            .entry(hll_key)
-                            .or_insert_with(|| HyperLogLog::new(DEFAULT_HLL_BITS));
+                        let hll = local_hll.entry(hll_key).or_insert_with(|| {
+                            // Only local lows are tracked; seeds are submitted directly
+                            HyperLogLog::builder()
+                                .bits(DEFAULT_HLL_BITS)
+                                .with_seeds(false)
+                                .build()
+                        });
                         if hll.add_hash(hash) {
                             improvements += 1;
                             let r2_key = executable.r2_key.clone();
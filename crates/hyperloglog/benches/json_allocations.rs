@@ -0,0 +1,79 @@
+//! Allocation counts for parsing a 20-bit sketch's JSON register array.
+//!
+//! Compares collecting the array into a `Vec<String>` before parsing, as
+//! `from_json` used to, with the streaming parse it does now. Run with
+//! `cargo bench -p hyperloglog --bench json_allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use hyperloglog::{HyperLogLog, MAX_HLL_BITS};
+
+/// Counts every allocation made through the global allocator.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations and wall time spent in `parse`.
+fn measure<T>(parse: impl FnOnce() -> T) -> (usize, f64) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    black_box(parse());
+    let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+    (ALLOCATIONS.load(Ordering::Relaxed) - before, elapsed)
+}
+
+fn main() {
+    let bits = MAX_HLL_BITS;
+    let mut hll = HyperLogLog::new(bits);
+    let mut state = 0u64;
+    for seed in 0..(4u64 << bits) {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hll.add(seed, z ^ (z >> 31));
+    }
+    let json = hll.to_json();
+
+    let (collected, collected_ms) = measure(|| {
+        let values: Vec<String> = serde_json::from_str(&json).unwrap();
+        let hashes: Vec<u64> = values.iter().map(|value| value.parse().unwrap()).collect();
+        HyperLogLog::from_parts(bits, hashes, None).unwrap()
+    });
+    let (streamed, streamed_ms) = measure(|| HyperLogLog::from_json(bits, &json));
+
+    println!("{}-bit sketch, {} registers", bits, hll.hashes().len());
+    println!(
+        "Vec<String> then parse: {:>9} allocations, {:>8.1} ms",
+        collected, collected_ms
+    );
+    println!(
+        "streaming from_json:    {:>9} allocations, {:>8.1} ms",
+        streamed, streamed_ms
+    );
+
+    assert!(HyperLogLog::from_json(bits, &json).same_hashes(&hll));
+    assert!(streamed * 1000 < collected);
+}
@@ -0,0 +1,117 @@
+//! Empirical bias correction for cardinality estimates.
+//!
+//! The harmonic estimator has a known bias while the sketch is only
+//! partially filled. Rather than hardcoding corrections, a [`BiasTable`]
+//! holds a measured curve that [`HyperLogLog::count`](crate::HyperLogLog::count)
+//! subtracts from its raw estimate, so corrections measured for a particular
+//! hash function or workload can be supplied through
+//! [`HyperLogLog::with_bias_table`](crate::HyperLogLog::with_bias_table).
+
+/// A correction curve mapping raw estimates to their measured bias.
+///
+/// Points are `(raw / m, bias / m)` pairs, normalized by the register count
+/// `m` so one table serves every bit width. The bias at a raw estimate is
+/// linearly interpolated between the surrounding points and subtracted from
+/// the estimate; outside the table's range no correction is applied, and a
+/// corrected estimate never drops below zero.
+///
+/// The default table is empty. With empty registers counted in the harmonic
+/// sum, the min-hash estimator is the standard HyperLogLog one, whose
+/// remaining low-range bias is better handled by
+/// [`HyperLogLog::count_with_small_range_correction`](crate::HyperLogLog::count_with_small_range_correction).
+///
+/// # Example
+///
+/// ```
+/// use hyperloglog::BiasTable;
+///
+/// let table = BiasTable::new(vec![(1.0, 0.2), (3.0, 0.0)]);
+/// assert_eq!(table.correct(2.0 * 256.0, 256), 2.0 * 256.0 - 0.1 * 256.0);
+/// assert_eq!(table.correct(4.0 * 256.0, 256), 4.0 * 256.0);
+/// assert_eq!(BiasTable::default().correct(123.0, 256), 123.0);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BiasTable {
+    points: Vec<(f64, f64)>,
+}
+
+impl BiasTable {
+    /// Build a table from `(raw / m, bias / m)` points in any order.
+    ///
+    /// Points with non-finite coordinates are dropped.
+    pub fn new(mut points: Vec<(f64, f64)>) -> Self {
+        points.retain(|&(raw, bias)| raw.is_finite() && bias.is_finite());
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points }
+    }
+
+    /// The table's points, sorted by normalized raw estimate.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Bytes allocated for the table's points.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.points.capacity() * std::mem::size_of::<(f64, f64)>()
+    }
+
+    /// Measured bias at `raw` for a sketch with `registers` registers.
+    pub fn bias(&self, raw: f64, registers: usize) -> f64 {
+        let m = registers as f64;
+        let x = raw / m;
+
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return 0.0,
+        };
+        if x < first.0 || x > last.0 {
+            return 0.0;
+        }
+
+        let upper = self.points.partition_point(|&(point, _)| point < x);
+        if upper == 0 {
+            return first.1 * m;
+        }
+        let (x0, y0) = self.points[upper - 1];
+        let (x1, y1) = self.points[upper];
+        if x1 == x0 {
+            return y1 * m;
+        }
+        (y0 + (y1 - y0) * (x - x0) / (x1 - x0)) * m
+    }
+
+    /// Subtract the measured bias from `raw`, never going below zero.
+    pub fn correct(&self, raw: f64, registers: usize) -> f64 {
+        (raw - self.bias(raw, registers)).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bias_interpolates_between_points() {
+        let table = BiasTable::new(vec![(4.0, 0.0), (0.0, 1.0), (2.0, 0.5)]);
+        assert_eq!(table.points(), &[(0.0, 1.0), (2.0, 0.5), (4.0, 0.0)]);
+
+        let m = 64;
+        assert_eq!(table.bias(0.0, m), 64.0);
+        assert_eq!(table.bias(64.0, m), 0.75 * 64.0);
+        assert_eq!(table.bias(128.0, m), 32.0);
+        assert_eq!(table.bias(192.0, m), 16.0);
+        assert_eq!(table.bias(256.0, m), 0.0);
+
+        // Outside the measured range nothing is corrected
+        assert_eq!(table.bias(257.0, m), 0.0);
+        assert_eq!(BiasTable::new(vec![(1.0, 0.5)]).bias(10.0, m), 0.0);
+    }
+
+    #[test]
+    fn test_correct_never_goes_negative() {
+        let table = BiasTable::new(vec![(0.0, 2.0), (1.0, 2.0), (f64::NAN, 1.0)]);
+        assert_eq!(table.points().len(), 2);
+        assert_eq!(table.correct(10.0, 16), 0.0);
+        assert_eq!(BiasTable::new(vec![(1.0, 0.5)]).correct(16.0, 16), 8.0);
+    }
+}
@@ -0,0 +1,188 @@
+//! Pluggable seed hashing.
+//!
+//! A register only proves work if its seed hashes to the stored value, so
+//! every caller needs the same seed-to-hash mapping. [`SeedHasher`] names
+//! that mapping, [`SplitMix64`] provides a standard one, and
+//! [`HyperLogLogWith`] keeps a hasher next to the sketch so seeds can be
+//! added directly.
+
+use std::ops::Deref;
+
+use crate::HyperLogLog;
+
+/// Maps a seed to the hash it proves.
+///
+/// Implemented for every `Fn(u64) -> u64`, so closures and plain functions
+/// work as hashers. The trait is object safe, so tests can swap hash
+/// functions behind a `&dyn SeedHasher` or `Box<dyn SeedHasher>`.
+pub trait SeedHasher {
+    /// Hash `seed`.
+    fn hash_seed(&self, seed: u64) -> u64;
+}
+
+impl<F: Fn(u64) -> u64> SeedHasher for F {
+    fn hash_seed(&self, seed: u64) -> u64 {
+        self(seed)
+    }
+}
+
+impl SeedHasher for Box<dyn SeedHasher> {
+    fn hash_seed(&self, seed: u64) -> u64 {
+        (**self).hash_seed(seed)
+    }
+}
+
+/// The splitmix64 finalizer, a fast, well-mixed 64-bit hash.
+///
+/// # Example
+///
+/// ```
+/// use hyperloglog::{HyperLogLog, SeedHasher, SplitMix64};
+///
+/// let mut hll = HyperLogLog::new(8);
+/// for seed in 0..1000 {
+///     hll.add_seed(seed, &SplitMix64);
+/// }
+///
+/// assert_eq!(SplitMix64.hash_seed(0), 0xe220a8397b1dcdaf);
+/// assert_eq!(hll.verify_work(|seed| SplitMix64.hash_seed(seed)), Ok(()));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SplitMix64;
+
+impl SeedHasher for SplitMix64 {
+    fn hash_seed(&self, seed: u64) -> u64 {
+        let mut x = seed.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+}
+
+/// A [`HyperLogLog`] bundled with the hasher its seeds are proven with.
+///
+/// Dereferences to the sketch for every read-only operation. Registers are
+/// only changed through [`HyperLogLogWith::add_seed`], so every hash in the
+/// sketch came from the stored hasher.
+///
+/// # Example
+///
+/// ```
+/// use hyperloglog::{HyperLogLogWith, SplitMix64};
+///
+/// let mut hll = HyperLogLogWith::new(12, SplitMix64);
+/// for seed in 0..10_000 {
+///     hll.add_seed(seed);
+/// }
+/// assert!(hll.count() > 0.0);
+/// assert_eq!(hll.verify(), Ok(()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct HyperLogLogWith<H> {
+    sketch: HyperLogLog,
+    hasher: H,
+}
+
+impl<H: SeedHasher> HyperLogLogWith<H> {
+    /// Create an empty sketch with `2^bits` registers, clamped like
+    /// [`HyperLogLog::new`].
+    pub fn new(bits: u8, hasher: H) -> Self {
+        Self::from_sketch(HyperLogLog::new(bits), hasher)
+    }
+
+    /// Attach `hasher` to an existing sketch.
+    ///
+    /// The sketch's registers are taken as-is; use
+    /// [`HyperLogLogWith::verify`] to check they came from this hasher.
+    pub fn from_sketch(sketch: HyperLogLog, hasher: H) -> Self {
+        Self { sketch, hasher }
+    }
+
+    /// Hash `seed` and add it. Returns `true` if its register improved.
+    pub fn add_seed(&mut self, seed: u64) -> bool {
+        self.sketch.add_seed(seed, &self.hasher)
+    }
+
+    /// Check every filled register against the stored hasher, as
+    /// [`HyperLogLog::verify_work`] does.
+    pub fn verify(&self) -> Result<(), crate::VerifyError> {
+        self.sketch.verify_work(|seed| self.hasher.hash_seed(seed))
+    }
+
+    /// The hasher seeds are proven with.
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// The underlying sketch.
+    pub fn sketch(&self) -> &HyperLogLog {
+        &self.sketch
+    }
+
+    /// Detach the sketch, e.g. to serialize or merge it.
+    pub fn into_sketch(self) -> HyperLogLog {
+        self.sketch
+    }
+}
+
+impl<H> Deref for HyperLogLogWith<H> {
+    type Target = HyperLogLog;
+
+    fn deref(&self) -> &HyperLogLog {
+        &self.sketch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closures_and_named_hashers_agree() {
+        let closure = |seed: u64| SplitMix64.hash_seed(seed);
+        let mut by_closure = HyperLogLog::new(8);
+        let mut by_struct = HyperLogLogWith::new(8, SplitMix64);
+        for seed in 0..500 {
+            by_closure.add_seed(seed, &closure);
+            by_struct.add_seed(seed);
+        }
+
+        assert_eq!(*by_struct, by_closure);
+        assert_eq!(by_struct.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_hasher_can_be_swapped_behind_trait_object() {
+        let hashers: Vec<Box<dyn SeedHasher>> = vec![
+            Box::new(SplitMix64),
+            Box::new(|seed: u64| seed.wrapping_mul(0x9e3779b97f4a7c15)),
+        ];
+
+        for hasher in hashers {
+            let expected: Vec<u64> = (0..64).map(|seed| hasher.hash_seed(seed)).collect();
+            let mut hll = HyperLogLogWith::new(6, hasher);
+            for seed in 0..64 {
+                hll.add_seed(seed);
+            }
+
+            assert_eq!(hll.verify(), Ok(()));
+            for (seed, &hash) in expected.iter().enumerate() {
+                assert!(
+                    hll.hashes()[(hash & 0x3F) as usize] <= hash,
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_sketch_verifies_against_its_hasher() {
+        let mut hll = HyperLogLog::new(4);
+        hll.add(1, 0x10);
+
+        let attached = HyperLogLogWith::from_sketch(hll.clone(), SplitMix64);
+        assert!(attached.verify().is_err());
+        assert_eq!(attached.into_sketch(), hll);
+    }
+}
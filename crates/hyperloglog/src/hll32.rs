@@ -0,0 +1,347 @@
+//! 32-bit hash variant of the min-hash HyperLogLog.
+//!
+//! Some test functions naturally produce 32-bit outputs. Storing those in
+//! `u64` registers wastes half the hash memory, so [`HyperLogLog32`] keeps
+//! `u32` registers with the same register selection and estimator as
+//! [`HyperLogLog`](crate::HyperLogLog). Seeds are still `u64`, so a register
+//! takes 12 bytes instead of 16: the sketch is a quarter smaller, not half.
+//! The narrower width means `rho` can reach at most 32 leading zeros, which
+//! suits lighter proof-of-work.
+
+use crate::{harmonic_estimate, kahan_sum, HyperLogLog};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Min-hash HyperLogLog over 32-bit hashes.
+///
+/// Empty registers hold `u32::MAX`. Seeds are still full `u64` values since
+/// the seed space is independent of the hash width.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde")
+)]
+pub struct HyperLogLog32 {
+    bits: u8,
+    seeds: Vec<u64>,
+    hashes: Vec<u32>,
+}
+
+impl HyperLogLog32 {
+    /// Create a new 32-bit HyperLogLog with the specified number of bits.
+    ///
+    /// Values are clamped to the range `[1, MAX_HLL_BITS]`, like
+    /// [`HyperLogLog::new`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog32;
+    ///
+    /// let hll = HyperLogLog32::new(12); // 4096 registers
+    /// assert_eq!(hll.bits(), 12);
+    /// ```
+    pub fn new(bits: u8) -> Self {
+        let bits = HyperLogLog::normalize_bits(bits);
+        let m = 1usize << bits;
+        Self {
+            bits,
+            seeds: vec![0; m],
+            hashes: vec![u32::MAX; m],
+        }
+    }
+
+    /// Add a seed-hash pair.
+    ///
+    /// Returns `true` if this hash improved (lowered) the minimum for its
+    /// register, `false` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog32;
+    ///
+    /// let mut hll = HyperLogLog32::new(12);
+    /// assert!(hll.add(42, 0x0000_1234));
+    /// ```
+    pub fn add(&mut self, seed: u64, hash: u32) -> bool {
+        let mask = self.hashes.len() - 1;
+        let register = (hash as usize) & mask;
+
+        if hash < self.hashes[register] {
+            self.hashes[register] = hash;
+            self.seeds[register] = seed;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Add a hash without tracking its seed.
+    ///
+    /// Returns `true` if this hash improved the minimum for its register.
+    pub fn add_hash(&mut self, hash: u32) -> bool {
+        self.add(0, hash)
+    }
+
+    /// Estimate the cardinality (number of unique items) seen.
+    ///
+    /// Uses the same estimator as [`HyperLogLog::count`], with `rho` computed
+    /// over the 32-bit register values. Returns 0.0 if no hashes have been
+    /// added.
+    pub fn count(&self) -> f64 {
+        if self.hashes.iter().all(|&hash| hash == u32::MAX) {
+            return 0.0;
+        }
+
+        let sum = kahan_sum(self.hashes.iter().map(|&hash| {
+            if hash == u32::MAX {
+                // Register never updated - rho is 0, contributing 2^0
+                1.0
+            } else {
+                2_f64.powi(-(self.register_rho(hash) as i32))
+            }
+        }));
+
+        harmonic_estimate(self.bits, sum)
+    }
+
+    /// Compute `rho` for a register's minimum hash at 32-bit width.
+    fn register_rho(&self, hash: u32) -> u32 {
+        // Remove bits used for register selection
+        let remaining = hash >> self.bits;
+        // Count leading zeros in remaining bits, add 1 for 1-indexed rho
+        // Use saturating_sub to prevent underflow
+        remaining.leading_zeros().saturating_sub(self.bits as u32) + 1
+    }
+
+    /// Get the number of bits used for register selection.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Width of the stored hashes in bits (32).
+    pub fn hash_bit_width(&self) -> u32 {
+        u32::BITS
+    }
+
+    /// Deepest `rho` a register can reach: `hash_bit_width - bits + 1`.
+    pub fn max_rho(&self) -> u32 {
+        self.hash_bit_width() - self.bits as u32 + 1
+    }
+
+    /// Get a reference to the minimum hashes array.
+    ///
+    /// Each element is the minimum hash seen for that register,
+    /// or `u32::MAX` if no hash has been added to that register.
+    pub fn hashes(&self) -> &[u32] {
+        &self.hashes
+    }
+
+    /// Get a reference to the seeds array.
+    pub fn seeds(&self) -> &[u64] {
+        &self.seeds
+    }
+
+    /// Approximate heap and inline size of this sketch in bytes.
+    ///
+    /// Comparable to [`HyperLogLog::memory_usage`]: each register costs 4
+    /// bytes of hash and 8 of seed, against 8 and 8 there.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, HyperLogLog32};
+    ///
+    /// let narrow = HyperLogLog32::new(12);
+    /// assert!(narrow.memory_usage() >= 4096 * 12);
+    /// assert!(narrow.memory_usage() < HyperLogLog::new(12).memory_usage());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.hashes.capacity() * std::mem::size_of::<u32>()
+            + self.seeds.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// Create a 32-bit HyperLogLog from a JSON string.
+    ///
+    /// Accepts the same string-encoded array format as
+    /// [`HyperLogLog::from_json`]; values that don't fit in a `u32` leave the
+    /// register empty. Seeds are not restored from JSON.
+    pub fn from_json(bits: u8, json: &str) -> Self {
+        let parsed = serde_json::from_str::<Vec<String>>(json).ok();
+        let mut state = Self::new(bits);
+
+        if let Some(values) = parsed {
+            for (index, value) in values.iter().enumerate().take(state.hashes.len()) {
+                if let Ok(parsed_value) = value.parse::<u32>() {
+                    state.hashes[index] = parsed_value;
+                }
+            }
+        }
+
+        state
+    }
+
+    /// Serialize the hashes to a JSON string.
+    ///
+    /// Uses the same string-encoded array format as [`HyperLogLog::to_json`].
+    pub fn to_json(&self) -> String {
+        let values: Vec<String> = self.hashes.iter().map(|value| value.to_string()).collect();
+        serde_json::to_string(&values).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_HLL_BITS;
+
+    /// 32-bit finalizer from murmur3 for testing
+    fn fmix32(seed: u64) -> u32 {
+        let mut x = (seed as u32) ^ ((seed >> 32) as u32);
+        x = x.wrapping_add(0x9e37_79b9);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x85eb_ca6b);
+        x ^= x >> 13;
+        x = x.wrapping_mul(0xc2b2_ae35);
+        x ^ (x >> 16)
+    }
+
+    #[test]
+    fn test_new_creates_correct_size() {
+        let hll = HyperLogLog32::new(4);
+        assert_eq!(hll.bits(), 4);
+        assert_eq!(hll.hashes().len(), 16);
+        assert_eq!(hll.seeds().len(), 16);
+        assert!(hll.hashes().iter().all(|&h| h == u32::MAX));
+    }
+
+    #[test]
+    fn test_memory_usage_is_three_quarters_of_u64_registers() {
+        let narrow = HyperLogLog32::new(12);
+        let wide = HyperLogLog::new(12);
+        let narrow_registers = narrow.memory_usage() - std::mem::size_of::<HyperLogLog32>();
+        let wide_registers = (wide.hashes().len() + wide.seeds().len()) * 8;
+
+        assert_eq!(narrow_registers, 4096 * 12);
+        assert_eq!(narrow_registers * 4, wide_registers * 3);
+    }
+
+    #[test]
+    fn test_new_normalizes_bits() {
+        let hll = HyperLogLog32::new(0);
+        assert_eq!(hll.bits(), 1);
+
+        let hll = HyperLogLog32::new(30);
+        assert_eq!(hll.bits(), MAX_HLL_BITS);
+        assert_eq!(hll.hashes().len(), 1 << MAX_HLL_BITS);
+    }
+
+    #[test]
+    fn test_add_returns_true_on_improvement() {
+        let mut hll = HyperLogLog32::new(4);
+
+        assert!(hll.add(1, 0xFFFF_0000));
+        assert!(hll.add(2, 0x0000_0000));
+        assert!(!hll.add(3, 0xFFFF_0000));
+    }
+
+    #[test]
+    fn test_add_tracks_seed() {
+        let mut hll = HyperLogLog32::new(4);
+
+        hll.add(42, 0x0013);
+        assert_eq!(hll.seeds()[3], 42);
+
+        hll.add(99, 0x0003);
+        assert_eq!(hll.seeds()[3], 99);
+
+        hll.add_hash(0x0005);
+        assert_eq!(hll.seeds()[5], 0);
+    }
+
+    #[test]
+    fn test_count_empty_returns_zero() {
+        let hll = HyperLogLog32::new(12);
+        assert_eq!(hll.count(), 0.0);
+    }
+
+    #[test]
+    fn test_count_estimates_cardinality() {
+        let mut hll = HyperLogLog32::new(12);
+        let n = 10_000u64;
+
+        for seed in 0..n {
+            hll.add(seed, fmix32(seed));
+        }
+
+        // Same tolerance as the 64-bit suite
+        let estimate = hll.count();
+        let lower = n as f64 * 0.95;
+        let upper = n as f64 * 1.05;
+        assert!(
+            estimate > lower && estimate < upper,
+            "Expected estimate near {}, got {}",
+            n,
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut hll = HyperLogLog32::new(4);
+        hll.add(1, 0x1234);
+        hll.add(2, 0x5678_0002);
+
+        let restored = HyperLogLog32::from_json(4, &hll.to_json());
+        assert_eq!(hll.hashes(), restored.hashes());
+        assert_eq!(restored.seeds(), &[0u64; 16]);
+    }
+
+    #[test]
+    fn test_json_rejects_values_wider_than_u32() {
+        let json = r#"["100", "18446744073709551615"]"#;
+        let hll = HyperLogLog32::from_json(4, json);
+        assert_eq!(hll.hashes()[0], 100);
+        assert_eq!(hll.hashes()[1], u32::MAX);
+    }
+
+    #[test]
+    fn test_leading_zeros_safe_for_edge_cases() {
+        for bits in [1u8, 4, 12, MAX_HLL_BITS] {
+            let mut hll = HyperLogLog32::new(bits);
+
+            // All-zero hash has the maximum 32 leading zeros
+            hll.add(1, 0);
+            // Smallest non-zero remaining bits
+            hll.add(2, 1 << bits);
+            // Very high hash (few leading zeros)
+            hll.add(3, 0xFFFF_FFFE);
+
+            let rho = hll.register_rho(0);
+            assert!(rho <= 33, "rho {} exceeds 32-bit width", rho);
+            assert_eq!(rho, hll.max_rho());
+            assert!(hll.count() > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_matches_64_bit_sketch_on_widened_hashes() {
+        // Register selection only looks at the low bits, so widening a 32-bit
+        // hash to u64 lands it in the same register of the 64-bit sketch.
+        let mut narrow = HyperLogLog32::new(6);
+        let mut wide = HyperLogLog::new(6);
+        for seed in 0..500u64 {
+            let hash = fmix32(seed);
+            narrow.add(seed, hash);
+            wide.add(seed, hash as u64);
+        }
+
+        let filled_narrow = narrow.hashes().iter().filter(|&&h| h != u32::MAX).count();
+        let filled_wide = wide.hashes().iter().filter(|&&h| h != u64::MAX).count();
+        assert_eq!(filled_narrow, filled_wide);
+    }
+}
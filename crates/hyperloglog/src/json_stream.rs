@@ -0,0 +1,134 @@
+//! Streaming reader for the bare JSON register array.
+//!
+//! [`HyperLogLog::to_json`](crate::HyperLogLog::to_json) writes registers as
+//! an array of decimal strings. Deserializing that into a `Vec<String>`
+//! allocates once per register, over a million times for a 20-bit sketch, so
+//! [`read_registers`] walks the array with `serde_json::Deserializer` and
+//! parses each string straight into its register.
+
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// What [`read_registers`] saw in the array.
+pub(crate) struct RegisterArray {
+    /// Number of values in the array, including any past the last register.
+    pub(crate) found: usize,
+    /// First register whose value isn't a `u64`.
+    pub(crate) first_invalid: Option<usize>,
+}
+
+/// Stream a JSON array of string-encoded hashes into `hashes`.
+///
+/// Values past `hashes.len()` are counted but not stored, and values that
+/// don't parse as `u64` leave their register untouched. Fails unless the
+/// input is a single array of strings, possibly after writing some registers.
+pub(crate) fn read_registers(
+    json: &str,
+    hashes: &mut [u64],
+) -> Result<RegisterArray, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let array = RegisterSeq { hashes }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(array)
+}
+
+/// Writes each array element into the next register.
+struct RegisterSeq<'a> {
+    hashes: &'a mut [u64],
+}
+
+impl<'de> DeserializeSeed<'de> for RegisterSeq<'_> {
+    type Value = RegisterArray;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<RegisterArray, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RegisterSeq<'_> {
+    type Value = RegisterArray;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of string-encoded u64 hashes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RegisterArray, A::Error> {
+        let mut array = RegisterArray {
+            found: 0,
+            first_invalid: None,
+        };
+
+        while let Some(HashString(value)) = seq.next_element()? {
+            if let Some(register) = self.hashes.get_mut(array.found) {
+                match value {
+                    Some(hash) => *register = hash,
+                    None => {
+                        array.first_invalid.get_or_insert(array.found);
+                    }
+                }
+            }
+            array.found += 1;
+        }
+
+        Ok(array)
+    }
+}
+
+/// One array element, parsed without keeping the string.
+///
+/// Unescaped strings are borrowed from the input and escaped ones from the
+/// deserializer's scratch buffer, so no element allocates.
+struct HashString(Option<u64>);
+
+impl<'de> Deserialize<'de> for HashString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(HashStringVisitor)
+    }
+}
+
+struct HashStringVisitor;
+
+impl Visitor<'_> for HashStringVisitor {
+    type Value = HashString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string-encoded u64")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<HashString, E> {
+        Ok(HashString(value.parse().ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_registers_counts_extra_and_flags_invalid() {
+        let mut hashes = [u64::MAX; 2];
+        let array = read_registers(r#"["7", "x", "9", "y"]"#, &mut hashes).unwrap();
+
+        assert_eq!(hashes, [7, u64::MAX]);
+        assert_eq!(array.found, 4);
+        assert_eq!(array.first_invalid, Some(1));
+    }
+
+    #[test]
+    fn test_read_registers_accepts_escapes_and_rejects_non_strings() {
+        let mut hashes = [u64::MAX; 2];
+        let array = read_registers(r#"["\u0031\u0032", "34"]"#, &mut hashes).unwrap();
+        assert_eq!(hashes, [12, 34]);
+        assert_eq!(array.first_invalid, None);
+
+        for json in [r#"["1", 2]"#, r#"{"a": "1"}"#, r#"["1"] ["2"]"#, r#"["1""#] {
+            assert!(
+                read_registers(json, &mut [u64::MAX; 2]).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+}
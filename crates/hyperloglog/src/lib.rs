@@ -72,11 +72,36 @@
 //! | 16   | 65,536    | 1 MB    | ~0.4%          |
 //! | 20   | 1,048,576 | 16 MB   | ~0.1%          |
 //!
-//! The standard error is approximately `1.04 / sqrt(m)` where `m = 2^bits`.
+//! The standard error is approximately `1.04 / sqrt(m)` where `m = 2^bits`;
+//! [`HyperLogLog::relative_error_for_bits`] computes it for any width.
+//!
+//! To hash seeds inside the sketch instead of at every call site, pass a
+//! [`SeedHasher`] such as [`SplitMix64`] to [`HyperLogLog::add_seed`] or keep
+//! one alongside the sketch in a [`HyperLogLogWith`].
+//!
+//! For hash functions that only produce 32-bit outputs, [`HyperLogLog32`]
+//! stores `u32` registers. Seeds stay `u64`, so that takes 12 rather than 16
+//! bytes per register, a quarter off the memory in the table above.
+
+mod bias;
+mod hasher;
+mod hll32;
+mod json_stream;
+
+pub use bias::BiasTable;
+pub use hasher::{HyperLogLogWith, SeedHasher, SplitMix64};
+pub use hll32::HyperLogLog32;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+// Renamed in the manifest so the `serde` feature can keep its name while the
+// crate itself is always available to `json_stream`
+extern crate serde_crate as serde;
+
 /// Default number of bits for register selection (32 registers).
 ///
 /// This provides a compact representation with reasonable precision (~18% standard error).
@@ -90,25 +115,422 @@ pub const DEFAULT_HLL_BITS: u8 = 5;
 /// on precision improvement.
 pub const MAX_HLL_BITS: u8 = 20;
 
+/// Current version of the [`HyperLogLog::to_json_versioned`] envelope.
+pub const JSON_FORMAT_VERSION: u64 = 1;
+
+/// Error returned when a serialized sketch cannot be interpreted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input is not a JSON array of strings or a versioned envelope.
+    InvalidJson,
+    /// The serialized sketch uses a different number of bits than expected.
+    BitsMismatch { expected: u8, found: u8 },
+    /// The versioned envelope was written by a newer format than this crate reads.
+    UnsupportedVersion(u64),
+    /// The binary encoding is truncated or malformed.
+    InvalidBytes,
+    /// A register array doesn't have `2^bits` entries.
+    LengthMismatch { expected: usize, found: usize },
+    /// A hash was addressed to a register its low bits don't select.
+    RegisterMismatch { register: usize, hash: u64 },
+    /// A register value in a JSON array doesn't parse as a `u64`.
+    InvalidRegister { register: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidJson => write!(f, "sketch JSON must be an array of strings"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported sketch format version {}", version)
+            }
+            Self::InvalidBytes => write!(f, "binary sketch is truncated or malformed"),
+            Self::LengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "register count mismatch: expected {}, found {}",
+                    expected, found
+                )
+            }
+            Self::BitsMismatch { expected, found } => {
+                write!(
+                    f,
+                    "sketch bits mismatch: expected {}, found {}",
+                    expected, found
+                )
+            }
+            Self::RegisterMismatch { register, hash } => {
+                write!(f, "hash {} does not belong to register {}", hash, register)
+            }
+            Self::InvalidRegister { register } => {
+                write!(f, "register {} is not a u64 hash", register)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Error returned when two sketches cannot be combined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    /// The sketches use a different number of bits, so their registers don't line up.
+    BitsMismatch { expected: u8, found: u8 },
+    /// A batch operation was given no sketches.
+    Empty,
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BitsMismatch { expected, found } => {
+                write!(
+                    f,
+                    "sketch bits mismatch: expected {}, found {}",
+                    expected, found
+                )
+            }
+            Self::Empty => write!(f, "no sketches provided"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// First register that failed [`HyperLogLog::verify_work`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The register's seed doesn't hash to the stored value.
+    HashMismatch {
+        register: usize,
+        seed: u64,
+        stored: u64,
+        computed: u64,
+    },
+    /// The stored hash reproduces, but its low bits select another register.
+    WrongRegister { register: usize, hash: u64 },
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HashMismatch {
+                register,
+                seed,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "register {}: seed {} hashes to {}, but {} is stored",
+                register, seed, computed, stored
+            ),
+            Self::WrongRegister { register, hash } => {
+                write!(
+                    f,
+                    "register {}: hash {} belongs to another register",
+                    register, hash
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Summary of the registers changed by a merge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Number of registers whose minimum hash was lowered by the merge.
+    pub improved: usize,
+    /// Whether the merge stopped early, leaving improvements unapplied.
+    pub truncated: bool,
+    /// Number of registers refused by [`HyperLogLog::merge_verified`]
+    /// because their seed didn't prove their hash.
+    pub rejected: usize,
+}
+
+/// One register of a sketch, as yielded by [`HyperLogLog::registers`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Register {
+    /// Position of the register, selected by the low `bits` of its hashes.
+    pub index: usize,
+    /// Minimum hash seen for this register, `u64::MAX` when empty.
+    pub hash: u64,
+    /// Seed that produced `hash`, 0 when empty or not recorded.
+    pub seed: u64,
+}
+
+impl Register {
+    /// Whether no hash has been recorded for this register.
+    pub fn is_empty(&self) -> bool {
+        self.hash == u64::MAX
+    }
+}
+
+/// A cardinality estimate with its 95% confidence interval, from
+/// [`HyperLogLog::count_estimate`].
+///
+/// Displays as the value with its relative margin, e.g. `12345 (±3.2%)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Estimate {
+    /// Point estimate, equal to [`HyperLogLog::count`].
+    pub value: f64,
+    /// Lower end of the interval, never below zero.
+    pub lower: f64,
+    /// Upper end of the interval.
+    pub upper: f64,
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let margin = if self.value > 0.0 {
+            (self.upper - self.value) / self.value * 100.0
+        } else {
+            0.0
+        };
+        write!(f, "{:.0} (±{:.1}%)", self.value, margin)
+    }
+}
+
+/// Outcome of checking a sample of registers with [`HyperLogLog::probabilistic_verify`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    /// Number of filled registers that were re-hashed.
+    pub checked: usize,
+    /// Number of checked registers whose seed reproduced the stored hash.
+    pub passed: usize,
+    /// Indices of checked registers that failed, in ascending order.
+    pub failed_registers: Vec<usize>,
+    /// Probability that a sketch with a single forged register would have
+    /// been caught by this sample; 0.0 once any register has failed.
+    pub confidence: f64,
+}
+
+/// Step a splitmix64 generator, used for reproducible register sampling.
+fn splitmix_next(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Turn a register `2^(-rho)` sum into a cardinality estimate.
+///
+/// Shared by every sketch width so they apply the same bias correction.
+fn harmonic_estimate(bits: u8, sum: f64) -> f64 {
+    let m = 2_f64.powi(bits as i32);
+
+    // Bias correction factor (alpha_m)
+    let alpha = match bits {
+        4 => 0.673,
+        5 => 0.697,
+        6 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / m),
+    };
+
+    if sum == 0.0 {
+        0.0
+    } else {
+        alpha * m * m / sum
+    }
+}
+
+/// Sum `values` with Kahan (compensated) summation.
+///
+/// Register sums mix `1.0` for empty registers with terms as small as
+/// `2^-64`, so plain addition drops the small terms once the total grows;
+/// the compensation carries them until they are large enough to land.
+fn kahan_sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+    for value in values {
+        let adjusted = value - compensation;
+        let total = sum + adjusted;
+        compensation = (total - sum) - adjusted;
+        sum = total;
+    }
+    sum
+}
+
+/// Whether a tied register should take seed `theirs` over `mine`: the
+/// smaller recorded seed wins, and an unrecorded seed (0) never wins.
+fn prefers_seed(mine: u64, theirs: u64) -> bool {
+    theirs != 0 && (mine == 0 || theirs < mine)
+}
+
+/// Length of the [`HyperLogLog::to_bytes`] header: `bits` then a flag byte.
+const BYTES_HEADER_LEN: usize = 2;
+
+/// Header flag marking that seeds follow the hashes in the binary layout.
+const BYTES_FLAG_SEEDS: u8 = 1;
+
+/// Two-sided 95% normal quantile.
+const Z_95: f64 = 1.959_964;
+
+/// Approximate chi-squared quantile with `dof` degrees of freedom at the
+/// normal quantile `z` (Wilson–Hilferty).
+fn chi_squared_quantile(dof: f64, z: f64) -> f64 {
+    let c = 2.0 / (9.0 * dof);
+    dof * (1.0 - c + z * c.sqrt()).powi(3).max(0.0)
+}
+
+/// Fixed-point scale of the running rho sum: each filled register
+/// contributes `2^(RHO_SUM_SCALE_BITS - rho)`.
+///
+/// `rho` never exceeds 64, and with at most `2^MAX_HLL_BITS` registers the
+/// sum stays well inside a `u128`, so the running total is exact and
+/// independent of the order registers were updated in.
+const RHO_SUM_SCALE_BITS: u32 = 64;
+
+/// Seed storage for a sketch's registers.
+///
+/// Dense storage keeps one seed per register. Lazy storage only keeps the
+/// nonzero seeds that [`HyperLogLog::add`] records, for servers that track
+/// seeds on a small subset of registers. Disabled storage keeps none, for
+/// callers that only need cardinality.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde", untagged)
+)]
+enum Seeds {
+    Dense(Vec<u64>),
+    Lazy(LazySeeds),
+    Disabled,
+}
+
+/// Nonzero seeds by register, plus the dense array [`HyperLogLog::seeds`]
+/// builds from them on first use.
+///
+/// Every change to `recorded` drops the array, so it is only rebuilt when
+/// `seeds` is called again after the sketch changed.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde", transparent)
+)]
+struct LazySeeds {
+    recorded: HashMap<usize, u64>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    materialized: OnceLock<Vec<u64>>,
+}
+
+impl LazySeeds {
+    /// The dense seed array for `m` registers, built on first call.
+    fn materialize(&self, m: usize) -> &[u64] {
+        self.materialized.get_or_init(|| {
+            let mut seeds = vec![0; m];
+            for (&register, &seed) in &self.recorded {
+                seeds[register] = seed;
+            }
+            seeds
+        })
+    }
+
+    /// Change the recorded seeds, dropping any materialized array.
+    fn recorded_mut(&mut self) -> &mut HashMap<usize, u64> {
+        self.materialized.take();
+        &mut self.recorded
+    }
+}
+
+impl Seeds {
+    /// Seed for `register`, 0 if none was recorded.
+    fn get(&self, register: usize) -> u64 {
+        match self {
+            Self::Dense(seeds) => seeds[register],
+            Self::Lazy(seeds) => seeds.recorded.get(&register).copied().unwrap_or(0),
+            Self::Disabled => 0,
+        }
+    }
+
+    fn set(&mut self, register: usize, seed: u64) {
+        match self {
+            Self::Dense(seeds) => seeds[register] = seed,
+            Self::Lazy(seeds) => {
+                if seed == 0 {
+                    seeds.recorded_mut().remove(&register);
+                } else {
+                    seeds.recorded_mut().insert(register, seed);
+                }
+            }
+            Self::Disabled => {}
+        }
+    }
+
+    /// Empty storage of the same kind for a sketch with `m` registers.
+    fn empty_like(&self, m: usize) -> Self {
+        match self {
+            Self::Dense(_) => Self::Dense(vec![0; m]),
+            Self::Lazy(_) => Self::Lazy(LazySeeds::default()),
+            Self::Disabled => Self::Disabled,
+        }
+    }
+}
+
 /// Min-hash variant HyperLogLog for cardinality estimation.
 ///
 /// This structure maintains minimum hash values for each register (bucket),
 /// along with the seeds that produced those hashes. This enables both
 /// cardinality estimation and proof-of-work verification.
-#[derive(Clone, Debug, PartialEq)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+///
+/// The estimator's register sum and occupancy are maintained incrementally
+/// as registers change, so [`HyperLogLog::count`] is O(1). Handing out raw
+/// access through [`HyperLogLog::hashes_mut`] marks that accounting stale;
+/// `count` then falls back to a full scan until the next mutation resyncs it.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde")
+)]
 pub struct HyperLogLog {
     bits: u8,
-    seeds: Vec<u64>,
+    seeds: Seeds,
     hashes: Vec<u64>,
+    /// Running fixed-point `sum(2^-rho)` over filled registers.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    rho_sum: u128,
+    /// Number of registers holding a hash.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    filled: usize,
+    /// Whether `rho_sum` and `filled` reflect the registers.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    accounting_fresh: bool,
+    /// Correction curve applied to the raw estimate.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bias: BiasTable,
+}
+
+impl PartialEq for HyperLogLog {
+    fn eq(&self, other: &Self) -> bool {
+        // The running accounting is derived from the registers, so only the
+        // registers themselves decide equality.
+        self.bits == other.bits
+            && self.hashes == other.hashes
+            && (0..self.hashes.len()).all(|index| self.seed(index) == other.seed(index))
+    }
 }
 
 impl HyperLogLog {
     /// Normalize bits to valid range [1, MAX_HLL_BITS].
+    ///
+    /// Every `1 << bits` in the crate shifts a normalized width, so it can't
+    /// overflow whatever `bits` a caller passes.
     fn normalize_bits(bits: u8) -> u8 {
         bits.clamp(1, MAX_HLL_BITS)
     }
 
+    /// Mask selecting a hash's register from its low bits.
+    ///
+    /// Derived from the register count rather than shifting by `bits`.
+    fn register_mask(&self) -> usize {
+        self.hashes.len() - 1
+    }
+
     /// Create a new HyperLogLog with the specified number of bits.
     ///
     /// The `bits` parameter determines the number of registers: `2^bits`.
@@ -127,358 +549,4921 @@ impl HyperLogLog {
         let m = 1usize << bits;
         Self {
             bits,
-            seeds: vec![0; m],
+            seeds: Seeds::Dense(vec![0; m]),
             hashes: vec![u64::MAX; m],
+            rho_sum: 0,
+            filled: 0,
+            accounting_fresh: true,
+            bias: BiasTable::default(),
         }
     }
 
-    /// Add a seed-hash pair to the HyperLogLog.
-    ///
-    /// Returns `true` if this hash improved (lowered) the minimum for its
-    /// register, `false` otherwise.
-    ///
-    /// # Arguments
+    /// Create a HyperLogLog whose seeds are allocated only as they are recorded.
     ///
-    /// * `seed` - The seed value that produced this hash
-    /// * `hash` - The hash value to add
+    /// Hashes are still stored densely, but seeds live in a map that only
+    /// holds the nonzero seeds passed to [`HyperLogLog::add`]. This suits
+    /// callers that keep seeds for few registers, or none at all via
+    /// [`HyperLogLog::add_hash`]. The sketch otherwise behaves exactly like
+    /// one from [`HyperLogLog::new`] and compares equal to it when registers
+    /// and seeds match.
     ///
     /// # Example
     ///
     /// ```
     /// use hyperloglog::HyperLogLog;
     ///
-    /// let mut hll = HyperLogLog::new(12);
-    /// let improved = hll.add(42, 0x0000_1234_5678_9ABC);
-    /// assert!(improved); // First hash always improves from u64::MAX
+    /// let mut hll = HyperLogLog::new_lazy_seeds(12);
+    /// hll.add(42, 0x0000_0000_0000_1234);
+    /// assert_eq!(hll.seed(0x234), 42);
+    /// assert_eq!(hll.seed(0), 0);
     /// ```
-    pub fn add(&mut self, seed: u64, hash: u64) -> bool {
-        let mask = (1usize << self.bits) - 1;
-        let register = (hash as usize) & mask;
-
-        if hash < self.hashes[register] {
-            self.hashes[register] = hash;
-            self.seeds[register] = seed;
-            true
-        } else {
-            false
+    pub fn new_lazy_seeds(bits: u8) -> Self {
+        Self {
+            seeds: Seeds::Lazy(LazySeeds::default()),
+            ..Self::new(bits)
         }
     }
 
-    /// Add a hash without tracking its seed.
-    ///
-    /// This is a convenience method that sets the seed to 0. Useful when
-    /// seed tracking is not needed.
-    ///
-    /// Returns `true` if this hash improved the minimum for its register.
+    /// Start configuring a sketch, defaulting to [`DEFAULT_HLL_BITS`] with
+    /// seeds recorded.
     ///
     /// # Example
     ///
     /// ```
     /// use hyperloglog::HyperLogLog;
     ///
-    /// let mut hll = HyperLogLog::new(12);
-    /// let improved = hll.add_hash(0x0000_1234_5678_9ABC);
-    /// assert!(improved);
+    /// let hll = HyperLogLog::builder().bits(12).with_seeds(false).build();
+    /// assert_eq!(hll.bits(), 12);
+    /// assert!(hll.seeds().is_empty());
     /// ```
-    pub fn add_hash(&mut self, hash: u64) -> bool {
-        self.add(0, hash)
+    pub fn builder() -> HyperLogLogBuilder {
+        HyperLogLogBuilder::default()
     }
 
-    /// Estimate the cardinality (number of unique items) seen.
-    ///
-    /// Uses the HyperLogLog algorithm with bias correction factors.
-    /// Returns 0.0 if no hashes have been added.
-    ///
-    /// # Algorithm
+    /// An empty sketch with `bits` registers and the same seed storage as
+    /// this one.
+    fn empty_like(&self, bits: u8) -> Self {
+        let mut empty = Self::new(bits);
+        empty.seeds = self.seeds.empty_like(empty.hashes.len());
+        empty.bias = self.bias.clone();
+        empty
+    }
+
+    /// Create an empty sketch whose estimates are corrected by `table`.
     ///
-    /// For each register, we compute `rho` (position of first 1-bit after
-    /// removing register selection bits) and sum `2^(-rho)`. The harmonic
-    /// mean formula with bias correction gives the cardinality estimate.
+    /// [`HyperLogLog::count`] and the estimators built on it subtract the
+    /// table's measured bias from the raw harmonic estimate, so corrections
+    /// measured empirically for this variant can be tried without changing
+    /// the crate. [`HyperLogLog::new`] uses [`BiasTable::default`], which
+    /// applies none. The table is a local estimation setting: it is not
+    /// serialized, and sketches built from this one by
+    /// [`HyperLogLog::fold`] or [`HyperLogLog::split_registers`] inherit it.
     ///
     /// # Example
     ///
     /// ```
-    /// use hyperloglog::HyperLogLog;
-    ///
-    /// let mut hll = HyperLogLog::new(12);
+    /// use hyperloglog::{BiasTable, HyperLogLog};
     ///
-    /// // Add some hashes
-    /// for i in 0..1000u64 {
-    ///     let hash = i.wrapping_mul(0x9e3779b97f4a7c15);
-    ///     hll.add_hash(hash);
+    /// let table = BiasTable::new(vec![(0.0, 0.5), (1e6, 0.5)]);
+    /// let mut corrected = HyperLogLog::with_bias_table(8, table);
+    /// let mut plain = HyperLogLog::new(8);
+    /// for i in 0..10_000u64 {
+    ///     corrected.add_hash(i.wrapping_mul(0x9e3779b97f4a7c15));
+    ///     plain.add_hash(i.wrapping_mul(0x9e3779b97f4a7c15));
     /// }
     ///
-    /// let estimate = hll.count();
-    /// // Min-hash HLL provides an estimate of cardinality
-    /// assert!(estimate > 0.0);
+    /// // A constant bias of half a register count shifts every estimate by 128
+    /// assert!((plain.count() - corrected.count() - 128.0).abs() < 1e-6);
     /// ```
-    pub fn count(&self) -> f64 {
-        let m = (1u64 << self.bits) as f64;
-
-        // Bias correction factor (alpha_m)
-        let alpha = match self.bits {
-            4 => 0.673,
-            5 => 0.697,
-            6 => 0.709,
-            _ => 0.7213 / (1.0 + 1.079 / m),
-        };
-
-        let sum: f64 = self
-            .hashes
-            .iter()
-            .map(|&hash| {
-                if hash == u64::MAX {
-                    // Register never updated - contributes nothing
-                    0.0
-                } else {
-                    // Remove bits used for register selection
-                    let remaining = hash >> self.bits;
-                    // Count leading zeros in remaining bits, add 1 for 1-indexed rho
-                    // Use saturating_sub to prevent underflow
-                    let rho = remaining.leading_zeros().saturating_sub(self.bits as u32) + 1;
-                    2_f64.powi(-(rho as i32))
-                }
-            })
-            .sum();
-
-        if sum == 0.0 {
-            0.0
-        } else {
-            alpha * m * m / sum
+    pub fn with_bias_table(bits: u8, table: BiasTable) -> Self {
+        Self {
+            bias: table,
+            ..Self::new(bits)
         }
     }
 
-    /// Get the number of bits used for register selection.
-    pub fn bits(&self) -> u8 {
-        self.bits
+    /// The correction curve applied by [`HyperLogLog::count`].
+    pub fn bias_table(&self) -> &BiasTable {
+        &self.bias
     }
 
-    /// Get a reference to the minimum hashes array.
+    /// Rebuild a sketch from stored registers.
     ///
-    /// Each element is the minimum hash seen for that register,
-    /// or `u64::MAX` if no hash has been added to that register.
-    pub fn hashes(&self) -> &[u64] {
-        &self.hashes
-    }
-
-    /// Get a mutable reference to the minimum hashes array.
+    /// `bits` is clamped like [`HyperLogLog::new`], and `hashes` (and `seeds`
+    /// when given) must then have exactly `2^bits` entries. Missing seeds
+    /// default to 0, as if every register had been set with
+    /// [`HyperLogLog::add_hash`]. This is the one validated path for storage
+    /// code that keeps `bits` and the register arrays separately.
     ///
-    /// This allows direct manipulation of register values, useful when
-    /// reconstructing HLL state from storage.
-    pub fn hashes_mut(&mut self) -> &mut [u64] {
-        &mut self.hashes
-    }
-
-    /// Get a reference to the seeds array.
+    /// # Example
     ///
-    /// Each element is the seed that produced the minimum hash for that
-    /// register. Seeds are 0 for registers that haven't been updated or
-    /// where `add_hash` was used instead of `add`.
-    pub fn seeds(&self) -> &[u64] {
-        &self.seeds
+    /// ```
+    /// use hyperloglog::{HyperLogLog, ParseError};
+    ///
+    /// let hll = HyperLogLog::from_parts(1, vec![100, u64::MAX], None).unwrap();
+    /// assert_eq!(hll.hashes(), &[100, u64::MAX]);
+    ///
+    /// assert_eq!(
+    ///     HyperLogLog::from_parts(2, vec![100], None),
+    ///     Err(ParseError::LengthMismatch { expected: 4, found: 1 })
+    /// );
+    /// ```
+    pub fn from_parts(
+        bits: u8,
+        hashes: Vec<u64>,
+        seeds: Option<Vec<u64>>,
+    ) -> Result<Self, ParseError> {
+        let bits = Self::normalize_bits(bits);
+        let m = 1usize << bits;
+
+        for found in std::iter::once(hashes.len()).chain(seeds.as_ref().map(Vec::len)) {
+            if found != m {
+                return Err(ParseError::LengthMismatch { expected: m, found });
+            }
+        }
+
+        let mut state = Self {
+            bits,
+            seeds: Seeds::Dense(seeds.unwrap_or_else(|| vec![0; m])),
+            hashes,
+            rho_sum: 0,
+            filled: 0,
+            accounting_fresh: false,
+            bias: BiasTable::default(),
+        };
+        state.sync_accounting();
+        Ok(state)
     }
 
-    /// Create a HyperLogLog from a JSON string.
+    /// Add a seed-hash pair to the HyperLogLog.
     ///
-    /// The JSON should be an array of string-encoded u64 values (to avoid
-    /// JavaScript number precision issues). Seeds are not restored from JSON.
+    /// Returns `true` if this hash improved (lowered) the minimum for its
+    /// register, `false` otherwise.
     ///
     /// # Arguments
     ///
-    /// * `bits` - Number of bits for register selection
-    /// * `json` - JSON array of string-encoded hash values
+    /// * `seed` - The seed value that produced this hash
+    /// * `hash` - The hash value to add
     ///
     /// # Example
     ///
     /// ```
     /// use hyperloglog::HyperLogLog;
     ///
-    /// let json = r#"["18446744073709551615", "12345678901234567890"]"#;
-    /// let hll = HyperLogLog::from_json(1, json); // 2 registers for bits=1
+    /// let mut hll = HyperLogLog::new(12);
+    /// let improved = hll.add(42, 0x0000_1234_5678_9ABC);
+    /// assert!(improved); // First hash always improves from u64::MAX
     /// ```
-    pub fn from_json(bits: u8, json: &str) -> Self {
-        let parsed = serde_json::from_str::<Vec<String>>(json).ok();
-        let mut state = Self::new(bits);
+    pub fn add(&mut self, seed: u64, hash: u64) -> bool {
+        self.add_report(seed, hash).is_some()
+    }
 
-        if let Some(values) = parsed {
-            for (index, value) in values.iter().enumerate().take(state.hashes.len()) {
-                if let Ok(parsed_value) = value.parse::<u64>() {
-                    state.hashes[index] = parsed_value;
-                }
-            }
-        }
+    /// Add a seed-hash pair, reporting what it displaced.
+    ///
+    /// Returns the register's previous `(hash, seed)` if this hash improved
+    /// it, or `None` otherwise. A previously empty register reports
+    /// `(u64::MAX, 0)`. Useful for logging which registers are churning.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// assert_eq!(hll.add_report(1, 0x20), Some((u64::MAX, 0)));
+    /// assert_eq!(hll.add_report(2, 0x10), Some((0x20, 1)));
+    /// assert_eq!(hll.add_report(3, 0x30), None);
+    /// ```
+    pub fn add_report(&mut self, seed: u64, hash: u64) -> Option<(u64, u64)> {
+        let mask = self.register_mask();
+        let register = (hash as usize) & mask;
 
-        state
+        let previous = self.hashes[register];
+        if hash < previous {
+            let previous_seed = self.seed(register);
+            self.set_register(register, hash, seed);
+            Some((previous, previous_seed))
+        } else {
+            None
+        }
     }
 
-    /// Serialize the hashes to a JSON string.
+    /// Add a batch of `(seed, hash)` pairs.
     ///
-    /// Hashes are encoded as strings to avoid JavaScript number precision
-    /// issues with large u64 values. Seeds are not included in the output.
+    /// Equivalent to calling [`HyperLogLog::add`] on each pair in order.
+    /// Returns how many distinct registers ended the batch lower than they
+    /// started it; a register improved twice counts once.
     ///
     /// # Example
     ///
     /// ```
     /// use hyperloglog::HyperLogLog;
     ///
-    /// let mut hll = HyperLogLog::new(4); // 16 registers
-    /// hll.add_hash(0x1234);
-    ///
-    /// let json = hll.to_json();
-    /// assert!(json.starts_with('['));
-    /// assert!(json.ends_with(']'));
+    /// let mut hll = HyperLogLog::new(4);
+    /// // Register 1 improves twice, register 2 once, 0x31 loses to 0x11
+    /// let improved = hll.add_all([(1, 0x21), (2, 0x11), (3, 0x02), (4, 0x31)]);
+    /// assert_eq!(improved, 2);
     /// ```
-    pub fn to_json(&self) -> String {
-        let values: Vec<String> = self.hashes.iter().map(|value| value.to_string()).collect();
-        serde_json::to_string(&values).unwrap_or_else(|_| "[]".to_string())
+    pub fn add_all(&mut self, items: impl IntoIterator<Item = (u64, u64)>) -> usize {
+        let mask = self.register_mask();
+        let mut improved = HashSet::new();
+        for (seed, hash) in items {
+            if self.add(seed, hash) {
+                improved.insert((hash as usize) & mask);
+            }
+        }
+        improved.len()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Overwrite a register, keeping the running estimator accounting in sync.
+    fn set_register(&mut self, register: usize, hash: u64, seed: u64) {
+        self.sync_accounting();
 
-    /// Simple splitmix64 hash for testing
-    fn splitmix(mut x: u64) -> u64 {
-        x = x.wrapping_add(0x9e3779b97f4a7c15);
-        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
-        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
-        x ^ (x >> 31)
+        let previous = self.hashes[register];
+        self.rho_sum -= self.register_weight(previous);
+        self.rho_sum += self.register_weight(hash);
+        if previous == u64::MAX && hash != u64::MAX {
+            self.filled += 1;
+        } else if previous != u64::MAX && hash == u64::MAX {
+            self.filled -= 1;
+        }
+
+        self.hashes[register] = hash;
+        self.seeds.set(register, seed);
+    }
+
+    /// Fixed-point `2^-rho` contribution of a register holding `hash`.
+    fn register_weight(&self, hash: u64) -> u128 {
+        if hash == u64::MAX {
+            // Register never updated - contributes nothing
+            0
+        } else {
+            1u128 << (RHO_SUM_SCALE_BITS - self.register_rho(hash))
+        }
+    }
+
+    /// Recompute the rho sum and occupancy with a full register scan.
+    fn scan_accounting(&self) -> (u128, usize) {
+        self.hashes.iter().fold((0, 0), |(sum, filled), &hash| {
+            let weight = self.register_weight(hash);
+            (sum + weight, filled + usize::from(hash != u64::MAX))
+        })
+    }
+
+    /// Rebuild the running accounting if raw register access made it stale.
+    fn sync_accounting(&mut self) {
+        if !self.accounting_fresh {
+            let (rho_sum, filled) = self.scan_accounting();
+            self.rho_sum = rho_sum;
+            self.filled = filled;
+            self.accounting_fresh = true;
+        }
+    }
+
+    /// Add a hash without tracking its seed.
+    ///
+    /// This is a convenience method that sets the seed to 0. Useful when
+    /// seed tracking is not needed.
+    ///
+    /// Returns `true` if this hash improved the minimum for its register.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// let improved = hll.add_hash(0x0000_1234_5678_9ABC);
+    /// assert!(improved);
+    /// ```
+    pub fn add_hash(&mut self, hash: u64) -> bool {
+        self.add(0, hash)
+    }
+
+    /// Hash `seed` with `hash_fn` and add the pair.
+    ///
+    /// Returns `true` if the register improved, like [`HyperLogLog::add`].
+    /// Any `Fn(u64) -> u64` works, as does a [`SeedHasher`] such as
+    /// [`SplitMix64`] or a `dyn SeedHasher`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, SplitMix64};
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// assert!(hll.add_seed(7, &SplitMix64));
+    /// assert!(hll.add_seed(8, &|seed: u64| seed << 4));
+    /// ```
+    pub fn add_seed<H: SeedHasher + ?Sized>(&mut self, seed: u64, hash_fn: &H) -> bool {
+        self.add(seed, hash_fn.hash_seed(seed))
+    }
+
+    /// Clear a single register back to empty.
+    ///
+    /// Sets the register to `u64::MAX` with seed 0, e.g. to drop one
+    /// register that failed verification without discarding the rest of the
+    /// sketch. Returns `false` (and changes nothing) if `index` is out of
+    /// range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(42, 0x0003);
+    ///
+    /// assert!(hll.reset_register(3));
+    /// assert_eq!(hll.hashes()[3], u64::MAX);
+    /// assert!(!hll.reset_register(16));
+    /// ```
+    pub fn reset_register(&mut self, index: usize) -> bool {
+        if index >= self.hashes.len() {
+            return false;
+        }
+        self.set_register(index, u64::MAX, 0);
+        true
+    }
+
+    /// Clear every register back to empty, keeping the allocation.
+    ///
+    /// All hashes become `u64::MAX` and all seeds 0, with `bits` and the seed
+    /// storage mode unchanged. Reusing a sketch this way avoids reallocating
+    /// the register arrays, which reach 16 MB at [`MAX_HLL_BITS`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(42, 0x0003);
+    ///
+    /// hll.clear();
+    /// assert!(hll.is_empty());
+    /// assert_eq!(hll, HyperLogLog::new(4));
+    /// ```
+    pub fn clear(&mut self) {
+        self.hashes.fill(u64::MAX);
+        match &mut self.seeds {
+            Seeds::Dense(seeds) => seeds.fill(0),
+            Seeds::Lazy(seeds) => seeds.recorded_mut().clear(),
+            Seeds::Disabled => {}
+        }
+        self.rho_sum = 0;
+        self.filled = 0;
+        self.accounting_fresh = true;
+    }
+
+    /// Bring the sketch into normal form by zeroing the seed of every empty
+    /// register.
+    ///
+    /// An empty register (`u64::MAX`) has no proof behind it, so its seed
+    /// should be 0. Sketches assembled from untrusted parts, e.g. through
+    /// [`HyperLogLog::from_parts`], [`HyperLogLog::from_bytes`] or
+    /// [`HyperLogLog::hashes_mut`], can carry stray seeds there; after this,
+    /// two sketches with the same hashes and proofs compare equal.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::from_parts(1, vec![u64::MAX, 0x1], Some(vec![9, 7])).unwrap();
+    /// assert_ne!(hll, HyperLogLog::from_parts(1, vec![u64::MAX, 0x1], Some(vec![0, 7])).unwrap());
+    ///
+    /// hll.canonicalize();
+    /// assert_eq!(hll.seed(0), 0);
+    /// assert_eq!(hll.seed(1), 7);
+    /// ```
+    pub fn canonicalize(&mut self) {
+        let hashes = &self.hashes;
+        match &mut self.seeds {
+            Seeds::Dense(seeds) => {
+                for (seed, &hash) in seeds.iter_mut().zip(hashes) {
+                    if hash == u64::MAX {
+                        *seed = 0;
+                    }
+                }
+            }
+            Seeds::Lazy(seeds) => seeds
+                .recorded_mut()
+                .retain(|&register, _| hashes[register] != u64::MAX),
+            Seeds::Disabled => {}
+        }
+    }
+
+    /// Merge another sketch into this one, applying at most `max_changes`
+    /// register improvements.
+    ///
+    /// Registers are visited in index order and each one where `other` holds a
+    /// lower hash takes that hash and its seed. Once `max_changes` registers
+    /// have been improved the merge stops and the returned stats have
+    /// `truncated` set, so a latency-sensitive caller can bound the work done
+    /// per request and schedule the remainder. Calling again with the same
+    /// `other` continues where the previous call stopped.
+    ///
+    /// When both sketches hold the same hash under different seeds, the
+    /// numerically smaller seed is kept, except that an unrecorded seed (0)
+    /// never replaces a recorded one. Either seed proves the same work, so
+    /// this only makes merges reproducible regardless of argument order. Such
+    /// seed swaps don't count as improvements or against `max_changes`.
+    ///
+    /// Returns an error if the sketches use a different number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut local = HyperLogLog::new(4);
+    /// let mut remote = HyperLogLog::new(4);
+    /// remote.add(1, 0x01);
+    /// remote.add(2, 0x02);
+    ///
+    /// let stats = local.merge_capped(&remote, 1).unwrap();
+    /// assert_eq!(stats.improved, 1);
+    /// assert!(stats.truncated);
+    /// ```
+    pub fn merge_capped(
+        &mut self,
+        other: &HyperLogLog,
+        max_changes: usize,
+    ) -> Result<MergeStats, MergeError> {
+        self.check_compatible(other)?;
+
+        let mut stats = MergeStats::default();
+        for register in 0..self.hashes.len() {
+            let hash = other.hashes[register];
+            if hash < self.hashes[register] {
+                if stats.improved == max_changes {
+                    stats.truncated = true;
+                    break;
+                }
+                self.set_register(register, hash, other.seed(register));
+                stats.improved += 1;
+            } else if hash == self.hashes[register]
+                && hash != u64::MAX
+                && prefers_seed(self.seed(register), other.seed(register))
+            {
+                self.seeds.set(register, other.seed(register));
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Merge another sketch into this one.
+    ///
+    /// Each register keeps the lower of the two hashes along with the seed
+    /// from whichever sketch held it, so merging sketches built on separate
+    /// machines gives the sketch of all their work combined. Returns an error,
+    /// leaving this sketch unchanged, if the sketches use a different number
+    /// of bits since their registers wouldn't line up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, MergeError};
+    ///
+    /// let mut a = HyperLogLog::new(4);
+    /// let mut b = HyperLogLog::new(4);
+    /// a.add(1, 0x10);
+    /// b.add(2, 0x01);
+    ///
+    /// a.merge(&b).unwrap();
+    /// assert_eq!(a.seed(1), 2);
+    ///
+    /// assert_eq!(
+    ///     a.merge(&HyperLogLog::new(5)),
+    ///     Err(MergeError::BitsMismatch { expected: 4, found: 5 })
+    /// );
+    /// ```
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<(), MergeError> {
+        self.merge_with_stats(other).map(|_| ())
+    }
+
+    /// Merge another sketch, refusing registers held by duplicated seeds.
+    ///
+    /// A seed hashes to a single value, so an honest sketch can hold any seed
+    /// in at most one register. A seed found in two or more registers of
+    /// `other`, most often 0 from sketches built with
+    /// [`HyperLogLog::add_hash`], can't be credited as work, so those
+    /// registers are left out of the merge. Every other register merges as in
+    /// [`HyperLogLog::merge`], including its smaller-seed rule for tied hashes.
+    ///
+    /// Returns the refused seeds in ascending order, empty if `other` was
+    /// clean, or an error if the sketches use a different number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut upload = HyperLogLog::new(4);
+    /// upload.add_hash(0x01);
+    /// upload.add_hash(0x02);
+    /// upload.add(9, 0x03);
+    ///
+    /// let mut total = HyperLogLog::new(4);
+    /// assert_eq!(total.merge_dedup_seeds(&upload).unwrap(), vec![0]);
+    /// assert_eq!(total.hashes()[1], u64::MAX);
+    /// assert_eq!(total.hashes()[3], 0x03);
+    /// ```
+    pub fn merge_dedup_seeds(&mut self, other: &HyperLogLog) -> Result<Vec<u64>, MergeError> {
+        self.check_compatible(other)?;
+
+        let mut registers_per_seed: HashMap<u64, usize> = HashMap::new();
+        for (_, _, seed) in other.iter().filter(|&(_, hash, _)| hash != u64::MAX) {
+            *registers_per_seed.entry(seed).or_insert(0) += 1;
+        }
+
+        let mut refused: Vec<u64> = registers_per_seed
+            .into_iter()
+            .filter(|&(_, registers)| registers > 1)
+            .map(|(seed, _)| seed)
+            .collect();
+        refused.sort_unstable();
+
+        for register in 0..self.hashes.len() {
+            let hash = other.hashes[register];
+            let seed = other.seed(register);
+            if refused.binary_search(&seed).is_ok() {
+                continue;
+            }
+            if hash < self.hashes[register] {
+                self.set_register(register, hash, seed);
+            } else if hash == self.hashes[register]
+                && hash != u64::MAX
+                && prefers_seed(self.seed(register), seed)
+            {
+                self.seeds.set(register, seed);
+            }
+        }
+
+        Ok(refused)
+    }
+
+    /// Build the merge of two sketches without modifying either.
+    ///
+    /// Equivalent to cloning `a` and calling [`HyperLogLog::merge`] with `b`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut a = HyperLogLog::new(4);
+    /// let mut b = HyperLogLog::new(4);
+    /// a.add(1, 0x10);
+    /// b.add(2, 0x01);
+    ///
+    /// let both = HyperLogLog::union(&a, &b).unwrap();
+    /// assert_eq!(both.hashes()[0], 0x10);
+    /// assert_eq!(both.hashes()[1], 0x01);
+    /// ```
+    pub fn union(a: &HyperLogLog, b: &HyperLogLog) -> Result<HyperLogLog, MergeError> {
+        let mut merged = a.clone();
+        merged.merge(b)?;
+        Ok(merged)
+    }
+
+    /// Downsample to `2^new_bits` registers.
+    ///
+    /// A register's index is the low bits of its hash, so the registers whose
+    /// indices agree in their low `new_bits` collapse into one, keeping the
+    /// minimum hash and its seed. The result equals a sketch built natively at
+    /// `new_bits` from the same hashes, which lets sketches of different
+    /// widths be compared at a common resolution. Folding to the same width
+    /// returns an identical copy; seed storage mode is preserved.
+    ///
+    /// `new_bits` is clamped like [`HyperLogLog::new`]. Returns
+    /// [`MergeError::BitsMismatch`] if it exceeds this sketch's `bits`, since
+    /// folding cannot add resolution.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut wide = HyperLogLog::new(4);
+    /// wide.add(1, 0x31); // register 1
+    /// wide.add(2, 0x25); // register 5
+    ///
+    /// let narrow = wide.fold(2).unwrap();
+    /// assert_eq!(narrow.hashes()[1], 0x25);
+    /// assert_eq!(narrow.seed(1), 2);
+    /// assert!(wide.fold(5).is_err());
+    /// ```
+    pub fn fold(&self, new_bits: u8) -> Result<HyperLogLog, MergeError> {
+        let new_bits = Self::normalize_bits(new_bits);
+        if new_bits > self.bits {
+            return Err(MergeError::BitsMismatch {
+                expected: self.bits,
+                found: new_bits,
+            });
+        }
+
+        let mut folded = self.empty_like(new_bits);
+        let mask = folded.register_mask();
+        for (register, &hash) in self.hashes.iter().enumerate() {
+            let target = register & mask;
+            if hash < folded.hashes[target] {
+                folded.set_register(target, hash, self.seed(register));
+            }
+        }
+        Ok(folded)
+    }
+
+    /// Approximate bytes of memory held by this sketch.
+    ///
+    /// Counts the struct itself plus the heap allocations behind the hashes,
+    /// the seeds and any bias table, by capacity rather than length. Dense
+    /// seeds cost a `u64` per register, so a 12-bit sketch holds 32 KB of
+    /// hashes and 32 KB of seeds, the 64 KB in the crate-level precision
+    /// table. Lazy seeds cost roughly a map entry per
+    /// recorded seed (the map's exact bucket layout isn't visible, so this is
+    /// an estimate), and sketches built without seeds cost nothing for them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let dense = HyperLogLog::new(12);
+    /// let seedless = HyperLogLog::builder().bits(12).with_seeds(false).build();
+    /// assert!(dense.memory_usage() >= 2 * 4096 * 8);
+    /// assert!(seedless.memory_usage() < dense.memory_usage());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        let seeds = match &self.seeds {
+            Seeds::Dense(seeds) => seeds.capacity() * std::mem::size_of::<u64>(),
+            // Each bucket stores its entry plus one control byte, and the
+            // array `seeds()` built, if any, counts until the next change
+            Seeds::Lazy(seeds) => {
+                seeds.recorded.capacity() * (std::mem::size_of::<(usize, u64)>() + 1)
+                    + seeds
+                        .materialized
+                        .get()
+                        .map_or(0, |array| array.capacity() * std::mem::size_of::<u64>())
+            }
+            Seeds::Disabled => 0,
+        };
+
+        std::mem::size_of::<Self>()
+            + self.hashes.capacity() * std::mem::size_of::<u64>()
+            + seeds
+            + self.bias.heap_size()
+    }
+
+    /// Number of registers a merge into this sketch has to visit: `2^bits`.
+    ///
+    /// Merge time is linear in this count and independent of how full the
+    /// sketches are, so callers can decide up front whether to merge inline
+    /// or hand the work off, and time [`HyperLogLog::merge_with_stats`]
+    /// themselves against a budget. As a rough guide, an optimized native
+    /// build merges 2^12 registers in tens of microseconds, 2^16 in about a
+    /// millisecond and 2^20 in tens of milliseconds; expect WebAssembly to
+    /// take a few times longer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// assert_eq!(HyperLogLog::new(12).merge_cost_estimate(), 4096);
+    /// ```
+    pub fn merge_cost_estimate(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Merge another sketch into this one, reporting how many registers improved.
+    ///
+    /// Each register keeps the lower of the two hashes along with its seed.
+    /// Returns an error if the sketches use a different number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut a = HyperLogLog::new(4);
+    /// let mut b = HyperLogLog::new(4);
+    /// a.add(1, 0x10);
+    /// b.add(2, 0x01);
+    ///
+    /// let stats = a.merge_with_stats(&b).unwrap();
+    /// assert_eq!(stats.improved, 1);
+    /// ```
+    pub fn merge_with_stats(&mut self, other: &HyperLogLog) -> Result<MergeStats, MergeError> {
+        self.merge_capped(other, usize::MAX)
+    }
+
+    /// Registers where this sketch beats `server`, as `(index, hash, seed)`
+    /// tuples ready to submit.
+    ///
+    /// A register qualifies when its hash is strictly lower than the
+    /// server's; empty registers never do. Tuples are in index order and use
+    /// the same layout as [`HyperLogLog::iter`]. Submitting every tuple and
+    /// merging them into `server` yields `server` merged with this sketch.
+    ///
+    /// Returns [`MergeError::BitsMismatch`] if the sketches use a different
+    /// number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut local = HyperLogLog::new(4);
+    /// local.add(7, 0x21);
+    /// local.add(8, 0x32);
+    ///
+    /// let mut server = HyperLogLog::new(4);
+    /// server.add(1, 0x11); // already better than local's 0x21
+    ///
+    /// assert_eq!(local.improvements_to_submit(&server), Ok(vec![(2, 0x32, 8)]));
+    /// ```
+    pub fn improvements_to_submit(
+        &self,
+        server: &HyperLogLog,
+    ) -> Result<Vec<(usize, u64, u64)>, MergeError> {
+        self.check_compatible(server)?;
+
+        Ok(self
+            .iter()
+            .filter(|&(index, hash, _)| hash < server.hashes[index])
+            .collect())
+    }
+
+    /// Merge another sketch, accepting only registers whose work verifies.
+    ///
+    /// Merges like [`HyperLogLog::merge_with_stats`], but each register of
+    /// `other` that would change this sketch is first checked the way
+    /// [`HyperLogLog::verify_work`] does: its seed must hash to its hash
+    /// under `hash_fn`, and the hash must select that register. Registers
+    /// that fail are skipped and counted in [`MergeStats::rejected`], so an
+    /// aggregator can merge and audit an upload in one pass. Registers that
+    /// wouldn't change this sketch are not hashed.
+    ///
+    /// Returns an error if the sketches use a different number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hash_fn = |seed: u64| seed.wrapping_mul(0x9e3779b97f4a7c15);
+    /// let mut upload = HyperLogLog::new(4);
+    /// upload.add(3, hash_fn(3));
+    /// upload.add(99, 0x01); // forged: 99 doesn't hash to 0x01
+    ///
+    /// let mut aggregate = HyperLogLog::new(4);
+    /// let stats = aggregate.merge_verified(&upload, hash_fn).unwrap();
+    /// assert_eq!((stats.improved, stats.rejected), (1, 1));
+    /// assert_eq!(aggregate.verify_work(hash_fn), Ok(()));
+    /// ```
+    pub fn merge_verified(
+        &mut self,
+        other: &HyperLogLog,
+        hash_fn: impl Fn(u64) -> u64,
+    ) -> Result<MergeStats, MergeError> {
+        self.check_compatible(other)?;
+
+        let mut stats = MergeStats::default();
+        for register in 0..self.hashes.len() {
+            let hash = other.hashes[register];
+            let improves = hash < self.hashes[register];
+            let reseeds = hash == self.hashes[register]
+                && hash != u64::MAX
+                && prefers_seed(self.seed(register), other.seed(register));
+            if !improves && !reseeds {
+                continue;
+            }
+
+            if !other.register_verifies(register, &hash_fn) {
+                stats.rejected += 1;
+            } else if improves {
+                self.set_register(register, hash, other.seed(register));
+                stats.improved += 1;
+            } else {
+                self.seeds.set(register, other.seed(register));
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Merge sketches from several contributors, crediting each register's winner.
+    ///
+    /// Sources are considered in ascending contributor order, so the result
+    /// doesn't depend on the slice order. Each register takes the lowest hash
+    /// across this sketch and all sources; ties between sources follow the
+    /// seed rule of [`HyperLogLog::merge_capped`], then the smaller
+    /// contributor. A contributor is credited with a register when its hash
+    /// ends up there and is lower than what this sketch held before. Every
+    /// contributor appears in the result, with 0 if it won nothing.
+    ///
+    /// All sources are checked for matching bits before any register changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut alice = HyperLogLog::new(4);
+    /// let mut bob = HyperLogLog::new(4);
+    /// alice.add(1, 0x0100_0000_0000_0001);
+    /// bob.add(2, 0x0001_0000_0000_0001);
+    /// bob.add(3, 0x0001_0000_0000_0002);
+    ///
+    /// let mut merged = HyperLogLog::new(4);
+    /// let credit = merged.merge_attributed(&[("alice", alice), ("bob", bob)]).unwrap();
+    /// assert_eq!(credit["alice"], 0);
+    /// assert_eq!(credit["bob"], 2);
+    /// ```
+    pub fn merge_attributed<C>(
+        &mut self,
+        sources: &[(C, HyperLogLog)],
+    ) -> Result<HashMap<C, usize>, MergeError>
+    where
+        C: Clone + Eq + std::hash::Hash + Ord,
+    {
+        for (_, source) in sources {
+            self.check_compatible(source)?;
+        }
+
+        let mut ordered: Vec<&(C, HyperLogLog)> = sources.iter().collect();
+        ordered.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut credit: HashMap<C, usize> = sources
+            .iter()
+            .map(|(contributor, _)| (contributor.clone(), 0))
+            .collect();
+
+        for register in 0..self.hashes.len() {
+            let original = self.hashes[register];
+            let mut best: Option<&(C, HyperLogLog)> = None;
+            let (mut best_hash, mut best_seed) = (original, self.seed(register));
+
+            for &entry in &ordered {
+                let hash = entry.1.hashes[register];
+                let seed = entry.1.seed(register);
+                let wins = hash < best_hash
+                    || (hash == best_hash && hash != u64::MAX && prefers_seed(best_seed, seed));
+                if wins {
+                    best = Some(entry);
+                    best_hash = hash;
+                    best_seed = seed;
+                }
+            }
+
+            let Some((contributor, _)) = best else {
+                continue;
+            };
+            if best_hash < original {
+                self.set_register(register, best_hash, best_seed);
+                if let Some(count) = credit.get_mut(contributor) {
+                    *count += 1;
+                }
+            } else {
+                self.seeds.set(register, best_seed);
+            }
+        }
+
+        Ok(credit)
+    }
+
+    /// Merge another sketch and report the proof-of-work it contributed.
+    ///
+    /// Returns the increase in [`HyperLogLog::estimated_work`] caused by the
+    /// merge, i.e. the "you contributed X work" figure for an uploaded sketch.
+    /// A sketch that improves no registers contributes `0.0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut server = HyperLogLog::new(4);
+    /// let mut upload = HyperLogLog::new(4);
+    /// upload.add(7, 0x0000_0000_0000_0013);
+    ///
+    /// let contributed = server.merge_reporting_work(&upload).unwrap();
+    /// assert!(contributed > 0.0);
+    /// assert_eq!(server.merge_reporting_work(&upload).unwrap(), 0.0);
+    /// ```
+    pub fn merge_reporting_work(&mut self, other: &HyperLogLog) -> Result<f64, MergeError> {
+        let before = self.estimated_work();
+        let stats = self.merge_with_stats(other)?;
+        if stats.improved == 0 {
+            return Ok(0.0);
+        }
+        Ok(self.estimated_work() - before)
+    }
+
+    /// Merge a batch of sketches into a new one.
+    ///
+    /// Compatibility is checked with [`HyperLogLog::assert_compatible`] before
+    /// any registers are combined. Returns [`MergeError::Empty`] for an empty
+    /// slice since there is no width to build the result with.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut a = HyperLogLog::new(4);
+    /// let mut b = HyperLogLog::new(4);
+    /// a.add(1, 0x10);
+    /// b.add(2, 0x01);
+    ///
+    /// let merged = HyperLogLog::merge_many(&[a, b]).unwrap();
+    /// assert_eq!(merged.hashes()[0], 0x10);
+    /// assert_eq!(merged.hashes()[1], 0x01);
+    /// ```
+    pub fn merge_many(sketches: &[HyperLogLog]) -> Result<HyperLogLog, MergeError> {
+        let bits = Self::assert_compatible(sketches)?;
+        let mut merged = Self::new(bits);
+        for sketch in sketches {
+            merged.merge_with_stats(sketch)?;
+        }
+        Ok(merged)
+    }
+
+    /// Split into `n` sketches holding disjoint, contiguous register ranges.
+    ///
+    /// Piece `i` keeps registers `i * 2^bits / n .. (i + 1) * 2^bits / n`
+    /// with their seeds and leaves every other register empty, so the pieces
+    /// can be processed or serialized in parallel and
+    /// [`HyperLogLog::merge_many`] puts them back together exactly. `n` is
+    /// raised to at least 1; pieces beyond `2^bits` are simply empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(1, 0x10);
+    /// hll.add(2, 0x0F);
+    ///
+    /// let pieces = hll.split_registers(2);
+    /// assert_eq!(pieces[0].hashes()[0], 0x10);
+    /// assert_eq!(pieces[1].hashes()[15], 0x0F);
+    /// assert_eq!(HyperLogLog::merge_many(&pieces).unwrap(), hll);
+    /// ```
+    pub fn split_registers(&self, n: usize) -> Vec<HyperLogLog> {
+        let n = n.max(1);
+        let m = self.hashes.len();
+
+        (0..n)
+            .map(|piece| {
+                let mut part = self.empty_like(self.bits);
+                for register in piece * m / n..(piece + 1) * m / n {
+                    let hash = self.hashes[register];
+                    if hash != u64::MAX {
+                        part.set_register(register, hash, self.seed(register));
+                    }
+                }
+                part
+            })
+            .collect()
+    }
+
+    /// Fold sketches from an iterator into a new one as they arrive.
+    ///
+    /// Unlike [`HyperLogLog::merge_many`] this never holds more than one
+    /// incoming sketch at a time, which suits long-running collectors reading
+    /// from a channel. Each item is checked against `bits` (clamped like
+    /// [`HyperLogLog::new`]) and the first mismatch aborts the fold. An empty
+    /// iterator yields an empty sketch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let sketches = (0..4u64).map(|seed| {
+    ///     let mut hll = HyperLogLog::new(4);
+    ///     hll.add(seed, seed << 8 | seed);
+    ///     hll
+    /// });
+    ///
+    /// let merged = HyperLogLog::merge_stream(4, sketches).unwrap();
+    /// assert_eq!(merged.filled_registers(), 4);
+    /// ```
+    pub fn merge_stream<I: IntoIterator<Item = HyperLogLog>>(
+        bits: u8,
+        iter: I,
+    ) -> Result<HyperLogLog, MergeError> {
+        let mut merged = Self::new(bits);
+        for sketch in iter {
+            merged.merge_with_stats(&sketch)?;
+        }
+        Ok(merged)
+    }
+
+    /// Check up front that a batch of sketches can be combined.
+    ///
+    /// Returns the common number of bits, or the first sketch whose bits
+    /// differ from the first one, so batch merges fail before touching any
+    /// registers rather than partway through.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, MergeError};
+    ///
+    /// let sketches = vec![HyperLogLog::new(8), HyperLogLog::new(8)];
+    /// assert_eq!(HyperLogLog::assert_compatible(&sketches), Ok(8));
+    ///
+    /// let mixed = vec![HyperLogLog::new(8), HyperLogLog::new(6)];
+    /// assert_eq!(
+    ///     HyperLogLog::assert_compatible(&mixed),
+    ///     Err(MergeError::BitsMismatch { expected: 8, found: 6 })
+    /// );
+    /// ```
+    pub fn assert_compatible(sketches: &[HyperLogLog]) -> Result<u8, MergeError> {
+        let (first, rest) = sketches.split_first().ok_or(MergeError::Empty)?;
+        for sketch in rest {
+            first.check_compatible(sketch)?;
+        }
+        Ok(first.bits)
+    }
+
+    /// Ensure `other` has the same number of bits as this sketch.
+    fn check_compatible(&self, other: &HyperLogLog) -> Result<(), MergeError> {
+        if self.bits == other.bits {
+            Ok(())
+        } else {
+            Err(MergeError::BitsMismatch {
+                expected: self.bits,
+                found: other.bits,
+            })
+        }
+    }
+
+    /// Estimate the cardinality (number of unique items) seen.
+    ///
+    /// Uses the HyperLogLog algorithm with bias correction factors.
+    /// Returns 0.0 if no hashes have been added.
+    ///
+    /// # Algorithm
+    ///
+    /// For each register, we compute `rho` (position of first 1-bit after
+    /// removing register selection bits) and sum `2^(-rho)`; empty registers
+    /// have `rho = 0` and contribute `1`. The harmonic mean formula with bias
+    /// correction gives the cardinality estimate. Keeping the register's
+    /// minimum hash keeps its maximum `rho`, so this is the standard
+    /// HyperLogLog estimator with its ~`1.04 / sqrt(2^bits)` standard error
+    /// (about 1.6% at 12 bits) once most registers are filled.
+    ///
+    /// The sum is maintained incrementally as registers change, so this is
+    /// O(1) unless [`HyperLogLog::hashes_mut`] was used since the last
+    /// mutation, in which case the registers are rescanned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    ///
+    /// // Add some hashes
+    /// for i in 0..1000u64 {
+    ///     let hash = i.wrapping_mul(0x9e3779b97f4a7c15);
+    ///     hll.add_hash(hash);
+    /// }
+    ///
+    /// let estimate = hll.count();
+    /// // Min-hash HLL provides an estimate of cardinality
+    /// assert!(estimate > 0.0);
+    /// ```
+    pub fn count(&self) -> f64 {
+        let (rho_sum, filled) = self.current_accounting();
+        self.estimate_from_accounting(rho_sum, filled)
+    }
+
+    /// Estimate the cardinality with the standard small-range correction.
+    ///
+    /// While few registers are filled, the harmonic estimate of
+    /// [`HyperLogLog::count`] is dominated by the empty registers and runs
+    /// far too high: a single item at 12 bits reads as thousands. As in
+    /// standard HyperLogLog, when the raw estimate is below `2.5 * m` and
+    /// some registers are still empty, this switches to linear counting,
+    /// `m * ln(m / empty)`, which only depends on occupancy. Above that
+    /// threshold, or once every register is filled, it equals `count()`.
+    ///
+    /// A register of the min-hash variant is empty exactly when a standard
+    /// HyperLogLog register would be, since both are selected by the same
+    /// low hash bits, so linear counting carries over unchanged. The stored
+    /// hashes themselves play no part below the threshold; the seeds still
+    /// prove the work, but the estimate comes from how many registers hold
+    /// one. Hashes are 64 bits wide, so the large-range correction that
+    /// 32-bit implementations apply near `2^32` isn't needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// for i in 0..10u64 {
+    ///     hll.add_hash(i.wrapping_mul(0x9e3779b97f4a7c15));
+    /// }
+    ///
+    /// assert!(hll.count() > 1000.0);
+    /// assert!((hll.count_with_small_range_correction() - 10.0).abs() < 1.0);
+    /// ```
+    pub fn count_with_small_range_correction(&self) -> f64 {
+        let (rho_sum, filled) = self.current_accounting();
+        let raw = self.estimate_from_accounting(rho_sum, filled);
+
+        let m = self.hashes.len() as f64;
+        let empty = self.hashes.len() - filled;
+        if raw < 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// Whether two sketches' [`HyperLogLog::count`] estimates agree within
+    /// `rel_tol`, relative to the larger of the two.
+    ///
+    /// Meant for checking that sketches built from the same data along
+    /// different paths (direct adds, JSON, binary) give the same answer, so
+    /// every consumer of this crate reports one number. Two empty sketches
+    /// agree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(8);
+    /// for i in 0..500u64 {
+    ///     hll.add_hash(i.wrapping_mul(0x9e3779b97f4a7c15));
+    /// }
+    /// let restored = HyperLogLog::from_json(8, &hll.to_json());
+    ///
+    /// assert!(HyperLogLog::estimates_agree(&hll, &restored, 1e-12));
+    /// assert!(!HyperLogLog::estimates_agree(&hll, &HyperLogLog::new(8), 0.5));
+    /// ```
+    pub fn estimates_agree(a: &HyperLogLog, b: &HyperLogLog, rel_tol: f64) -> bool {
+        let (x, y) = (a.count(), b.count());
+        let scale = x.abs().max(y.abs());
+        scale == 0.0 || (x - y).abs() <= rel_tol * scale
+    }
+
+    /// A conservative cardinality figure that never overestimates.
+    ///
+    /// Every filled register holds the hash of at least one item, and no
+    /// item lands in two registers, so the number of filled registers is a
+    /// hard lower bound on the distinct items seen. [`HyperLogLog::count`] is
+    /// unbiased only in aggregate; on sparse sketches it can run well above
+    /// the truth, as a single deep hash already suggests many items. This
+    /// returns the occupancy, capped at the point estimate so it never
+    /// exceeds `count()`, which suits guarantees such as SLAs where claiming
+    /// too much work is worse than claiming too little.
+    ///
+    /// The bound is tight while the sketch is sparse and falls behind once
+    /// registers start collecting more than one item; with `2^bits`
+    /// registers it saturates there regardless of cardinality.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// hll.add_hash(0x1000); // very deep hash, large point estimate
+    /// hll.add_hash(0x0001);
+    /// assert_eq!(hll.count_lower_bound(), 2.0);
+    /// assert!(hll.count_lower_bound() <= hll.count());
+    /// ```
+    pub fn count_lower_bound(&self) -> f64 {
+        (self.filled_registers() as f64).min(self.count())
+    }
+
+    /// Estimate the cardinality from a full register scan with compensated
+    /// summation.
+    ///
+    /// [`HyperLogLog::count`] keeps its register sum in exact fixed point, so
+    /// it never loses the tiny `2^-rho` terms of a deep register even at
+    /// [`MAX_HLL_BITS`]. This recomputes the same sum from scratch in `f64`
+    /// using Kahan summation and agrees with `count()`; it is the reference a
+    /// floating-point implementation elsewhere (such as a browser client)
+    /// should match, where naive addition would drift at high bit counts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// for i in 0..1000u64 {
+    ///     hll.add_hash(i.wrapping_mul(0x9e3779b97f4a7c15));
+    /// }
+    /// assert_eq!(hll.count_stable(), hll.count());
+    /// ```
+    pub fn count_stable(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let sum = kahan_sum(self.hashes.iter().map(|&hash| {
+            if hash == u64::MAX {
+                1.0
+            } else {
+                2_f64.powi(-(self.register_rho(hash) as i32))
+            }
+        }));
+        self.bias
+            .correct(harmonic_estimate(self.bits, sum), self.hashes.len())
+    }
+
+    /// Expected relative standard error of [`HyperLogLog::count`] at this
+    /// sketch's width. See [`HyperLogLog::relative_error_for_bits`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hll = HyperLogLog::new(12);
+    /// println!("{:.0} ±{:.1}%", hll.count(), hll.relative_error() * 100.0);
+    /// ```
+    pub fn relative_error(&self) -> f64 {
+        Self::relative_error_for_bits(self.bits)
+    }
+
+    /// Expected relative standard error of the estimate with `2^bits`
+    /// registers: `1.04 / sqrt(2^bits)`.
+    ///
+    /// `bits` is clamped like [`HyperLogLog::new`], so the result describes
+    /// the sketch `HyperLogLog::new(bits)` would actually build.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// assert_eq!(HyperLogLog::relative_error_for_bits(4), 0.26);
+    /// assert_eq!(HyperLogLog::relative_error_for_bits(16), 1.04 / 256.0);
+    /// ```
+    pub fn relative_error_for_bits(bits: u8) -> f64 {
+        let bits = Self::normalize_bits(bits);
+        1.04 / 2_f64.powi(bits as i32).sqrt()
+    }
+
+    /// Estimate the cardinality with a 95% confidence interval.
+    ///
+    /// The value is [`HyperLogLog::count`]; the bounds are `1.96` standard
+    /// errors of [`HyperLogLog::relative_error`] either side of it, with the
+    /// lower bound clamped at zero. The interval assumes the asymptotic
+    /// error, so it is only trustworthy once most registers are filled; see
+    /// [`HyperLogLog::count_with_small_range_correction`] for sparse sketches.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, SplitMix64};
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// for seed in 0..100_000 {
+    ///     hll.add_seed(seed, &SplitMix64);
+    /// }
+    ///
+    /// let estimate = hll.count_estimate();
+    /// assert_eq!(estimate.value, hll.count());
+    /// assert!(estimate.lower < 100_000.0 && 100_000.0 < estimate.upper);
+    /// assert!(estimate.to_string().ends_with("(±3.2%)"));
+    /// ```
+    pub fn count_estimate(&self) -> Estimate {
+        let value = self.count();
+        let margin = Z_95 * self.relative_error() * value;
+        Estimate {
+            value,
+            lower: (value - margin).max(0.0),
+            upper: value + margin,
+        }
+    }
+
+    /// Change in [`HyperLogLog::count`] that adding `hash` would cause.
+    ///
+    /// Returns 0.0 if `hash` wouldn't lower its register. The sketch is not
+    /// modified, so a scheduler can rank candidate submissions by impact
+    /// before committing any of them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add_hash(0x0100_0000_0000_0003);
+    ///
+    /// let predicted = hll.estimate_delta_if_added(0x0000_0100_0000_0003);
+    /// let before = hll.count();
+    /// hll.add_hash(0x0000_0100_0000_0003);
+    /// assert_eq!(hll.count() - before, predicted);
+    ///
+    /// assert_eq!(hll.estimate_delta_if_added(0x0100_0000_0000_0003), 0.0);
+    /// ```
+    pub fn estimate_delta_if_added(&self, hash: u64) -> f64 {
+        let mask = self.register_mask();
+        let previous = self.hashes[(hash as usize) & mask];
+        if hash >= previous {
+            return 0.0;
+        }
+
+        let (rho_sum, filled) = self.current_accounting();
+        let after = rho_sum - self.register_weight(previous) + self.register_weight(hash);
+        let filled_after = filled + usize::from(previous == u64::MAX);
+        self.estimate_from_accounting(after, filled_after)
+            - self.estimate_from_accounting(rho_sum, filled)
+    }
+
+    /// Fixed-point rho sum and occupancy, from the running totals when fresh.
+    fn current_accounting(&self) -> (u128, usize) {
+        if self.accounting_fresh {
+            (self.rho_sum, self.filled)
+        } else {
+            self.scan_accounting()
+        }
+    }
+
+    /// Turn a fixed-point rho sum over `filled` registers into a cardinality
+    /// estimate.
+    ///
+    /// Empty registers have `rho = 0` and so contribute `2^0 = 1` each to
+    /// the harmonic sum; leaving them out inflates the estimate badly
+    /// whenever the sketch is not saturated.
+    fn estimate_from_accounting(&self, rho_sum: u128, filled: usize) -> f64 {
+        if filled == 0 {
+            return 0.0;
+        }
+        let empty = self.hashes.len() - filled;
+        let sum = rho_sum as f64 / 2_f64.powi(RHO_SUM_SCALE_BITS as i32) + empty as f64;
+        self.bias
+            .correct(harmonic_estimate(self.bits, sum), self.hashes.len())
+    }
+
+    /// Estimate the total number of hash attempts behind this sketch.
+    ///
+    /// # Statistical Model
+    ///
+    /// Each register's hash bits above the register selection bits are
+    /// uniform on `[0, 1)` when read as a fraction `u`. A register that has
+    /// seen `k` attempts holds the minimum of `k` such values, so a minimum
+    /// of `u` is evidence of roughly `1 / u` attempts — a hash with `z`
+    /// leading zeros stands for about `2^z` attempts. Under the usual
+    /// Poisson approximation the register minima are exponential with rate
+    /// `n / m`, their sum is Gamma distributed, and `m * (m - 1) / sum(u)` is
+    /// an unbiased estimate of the total attempts `n`. Empty registers count
+    /// as `u = 1`, so while most registers are still empty the estimate
+    /// cannot drop much below `m`.
+    ///
+    /// Unlike [`HyperLogLog::count`], which quantizes each register to its
+    /// leading-zero count, this uses the full precision of the stored
+    /// minimum. Returns 0.0 for an empty sketch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(8);
+    /// for seed in 0..1000u64 {
+    ///     hll.add(seed, seed.wrapping_mul(0x9e3779b97f4a7c15));
+    /// }
+    /// assert!(hll.estimated_work() > 0.0);
+    /// ```
+    pub fn estimated_work(&self) -> f64 {
+        let (filled, sum) = self.minima_sum();
+        if filled == 0 {
+            return 0.0;
+        }
+
+        let m = self.hashes.len() as f64;
+        m * (m - 1.0) / sum
+    }
+
+    /// Count filled registers and sum their minimum fractions `u`, with
+    /// empty registers counting as `u = 1`.
+    fn minima_sum(&self) -> (usize, f64) {
+        let range = 2_f64.powi(64 - self.bits as i32);
+        let mut filled = 0usize;
+
+        let sum = kahan_sum(self.hashes.iter().map(|&hash| {
+            if hash == u64::MAX {
+                1.0
+            } else {
+                filled += 1;
+                // +1 keeps an all-zero remainder from dividing by zero
+                ((hash >> self.bits) as f64 + 1.0) / range
+            }
+        }));
+
+        (filled, sum)
+    }
+
+    /// Confidence interval on [`HyperLogLog::estimated_work`] at `z`
+    /// standard normal deviations, e.g. `1.96` for 95%.
+    ///
+    /// Returns `(low, high)`, which always contains the point estimate.
+    /// An empty sketch returns `(0.0, 0.0)`.
+    ///
+    /// # Statistical Derivation
+    ///
+    /// Under the Poisson model of [`HyperLogLog::estimated_work`], a filled
+    /// register's minimum fraction `u` is exponential with rate `n / m`, so
+    /// its variance equals its squared mean: each register's depth is an
+    /// estimator as noisy as the quantity it measures. Summed over the `k`
+    /// filled registers, `2 * (n / m) * sum(u)` is chi-squared with `2k`
+    /// degrees of freedom, so the estimate, being inversely proportional to
+    /// that sum, carries a relative error of about `1 / sqrt(k)`. The
+    /// interval scales the point estimate by the chi-squared quantiles at
+    /// `±z` over their mean `2k` (Wilson–Hilferty approximation), which
+    /// makes it slightly right-skewed and widens it as the sketch gets
+    /// sparser.
+    ///
+    /// This covers the noise in the register minima only. While most
+    /// registers are empty the point estimate is also biased upward; see
+    /// [`HyperLogLog::plausible_count_range`] for an interval that accounts
+    /// for the empty registers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, SplitMix64};
+    ///
+    /// let mut hll = HyperLogLog::new(12);
+    /// for seed in 0..100_000 {
+    ///     hll.add_seed(seed, &SplitMix64);
+    /// }
+    ///
+    /// let (low, high) = hll.estimated_work_ci(1.96);
+    /// assert!(low < hll.estimated_work() && hll.estimated_work() < high);
+    /// assert!(low < 100_000.0 && 100_000.0 < high);
+    /// ```
+    pub fn estimated_work_ci(&self, z: f64) -> (f64, f64) {
+        let (filled, sum) = self.minima_sum();
+        if filled == 0 {
+            return (0.0, 0.0);
+        }
+
+        let m = self.hashes.len() as f64;
+        let estimate = m * (m - 1.0) / sum;
+        let dof = 2.0 * filled as f64;
+        let z = z.abs();
+        let low = estimate * chi_squared_quantile(dof, -z) / dof;
+        let high = estimate * chi_squared_quantile(dof, z) / dof;
+
+        (low.min(estimate), high.max(estimate))
+    }
+
+    /// Range of true cardinalities consistent with the registers at the 95% level.
+    ///
+    /// Returns `(low, high)`. Unlike a symmetric interval around
+    /// [`HyperLogLog::count`], the range is driven by how many registers are
+    /// filled: a sketch that is still mostly empty has seen few informative
+    /// minima, so the true count may lie far above the point estimate.
+    ///
+    /// # Statistical Basis
+    ///
+    /// Under the Poisson model of [`HyperLogLog::estimated_work`], each
+    /// register's minimum fraction `u` is exponential with rate `n / m`,
+    /// censored at 1 where the register is still empty. With `k` filled
+    /// registers and `S = sum(u)` (empties counting as 1), `2 * (n / m) * S`
+    /// is approximately chi-squared with `2k` degrees of freedom. Inverting
+    /// its 2.5% and 97.5% quantiles gives the range, whose relative width
+    /// shrinks like `1 / sqrt(k)` as occupancy rises. The low end is never
+    /// below `k`, since every filled register saw at least one item, and an
+    /// empty sketch returns `(0.0, 0.0)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(8);
+    /// for seed in 0..1000u64 {
+    ///     hll.add(seed, seed.wrapping_mul(0x9e3779b97f4a7c15));
+    /// }
+    ///
+    /// let (low, high) = hll.plausible_count_range();
+    /// assert!(low < hll.estimated_work() && hll.estimated_work() < high);
+    /// ```
+    pub fn plausible_count_range(&self) -> (f64, f64) {
+        let (filled, sum) = self.minima_sum();
+        if filled == 0 {
+            return (0.0, 0.0);
+        }
+
+        let m = self.hashes.len() as f64;
+        let dof = 2.0 * filled as f64;
+        let low = m * chi_squared_quantile(dof, -Z_95) / (2.0 * sum);
+        let high = m * chi_squared_quantile(dof, Z_95) / (2.0 * sum);
+
+        (low.max(filled as f64), high.max(filled as f64))
+    }
+
+    /// Score how plausibly this sketch came from honest hashing, in `[0, 1]`.
+    ///
+    /// Filling `k` of `m` registers honestly takes about
+    /// `-m * ln(1 - k / m)` attempts (with half a register of smoothing so a
+    /// full sketch stays finite), and those attempts also drive the register
+    /// minima down. The minima imply `m * k / sum(u)` attempts, the estimator
+    /// behind [`HyperLogLog::plausible_count_range`]. An honest sketch has the
+    /// two agree or the minima ahead, scoring 1.0. A sketch whose registers
+    /// are filled but shallow (e.g. any hash per register, picked without
+    /// searching) claims occupancy its minima can't support and scores close
+    /// to its ratio. Scores below about 0.5 warrant verification before the
+    /// sketch is trusted. An empty sketch scores 1.0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// // Every register filled with a shallow hash
+    /// let mut forged = HyperLogLog::new(8);
+    /// for register in 0..256u64 {
+    ///     forged.add_hash(0x8000_0000_0000_0000 | register);
+    /// }
+    /// assert!(forged.plausibility_score() < 0.5);
+    /// ```
+    pub fn plausibility_score(&self) -> f64 {
+        let (filled, sum) = self.minima_sum();
+        if filled == 0 {
+            return 1.0;
+        }
+
+        let m = self.hashes.len() as f64;
+        let k = filled as f64;
+        let from_minima = m * k / sum;
+        let from_occupancy = -m * ((m - k + 0.5) / m).ln();
+
+        (from_minima / from_occupancy).min(1.0)
+    }
+
+    /// Re-hash a random sample of filled registers to audit the sketch.
+    ///
+    /// Checks `ceil(sample_fraction * filled)` distinct filled registers,
+    /// confirming that `hash_fn(seed)` reproduces the stored hash and that
+    /// the hash maps to that register. This makes auditing a 1M-register
+    /// sketch tractable at the cost of only probabilistic coverage.
+    ///
+    /// Sampling uses a deterministic generator seeded from the register
+    /// contents, so the same sketch always checks the same registers and a
+    /// report can be reproduced by anyone. `sample_fraction` is clamped to
+    /// `[0, 1]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hash_fn = |seed: u64| seed.wrapping_mul(0x9e3779b97f4a7c15);
+    /// let mut hll = HyperLogLog::new(6);
+    /// for seed in 0..500u64 {
+    ///     hll.add(seed, hash_fn(seed));
+    /// }
+    ///
+    /// let report = hll.probabilistic_verify(0.25, hash_fn);
+    /// assert!(report.failed_registers.is_empty());
+    /// assert_eq!(report.passed, report.checked);
+    /// ```
+    pub fn probabilistic_verify(
+        &self,
+        sample_fraction: f64,
+        hash_fn: impl Fn(u64) -> u64,
+    ) -> VerifyReport {
+        let mut filled: Vec<usize> = self
+            .iter()
+            .filter(|&(_, hash, _)| hash != u64::MAX)
+            .map(|(index, _, _)| index)
+            .collect();
+        if filled.is_empty() {
+            return VerifyReport {
+                confidence: 1.0,
+                ..VerifyReport::default()
+            };
+        }
+
+        let fraction = sample_fraction.clamp(0.0, 1.0);
+        let sample_size = ((fraction * filled.len() as f64).ceil() as usize).min(filled.len());
+
+        // Seed the sampler from the registers so reports are reproducible
+        let mut rng = self.hashes.iter().fold(self.bits as u64, |acc, &hash| {
+            let mut state = acc ^ hash;
+            splitmix_next(&mut state)
+        });
+
+        // Partial Fisher-Yates shuffle picks `sample_size` distinct registers
+        for i in 0..sample_size {
+            let j = i + (splitmix_next(&mut rng) % (filled.len() - i) as u64) as usize;
+            filled.swap(i, j);
+        }
+
+        let mut report = VerifyReport {
+            checked: sample_size,
+            ..VerifyReport::default()
+        };
+        for &index in &filled[..sample_size] {
+            if self.register_verifies(index, &hash_fn) {
+                report.passed += 1;
+            } else {
+                report.failed_registers.push(index);
+            }
+        }
+        report.failed_registers.sort_unstable();
+        report.confidence = if report.failed_registers.is_empty() {
+            sample_size as f64 / filled.len() as f64
+        } else {
+            0.0
+        };
+
+        report
+    }
+
+    /// Re-hash every filled register to confirm the sketch's work is genuine.
+    ///
+    /// For each non-empty register, in index order, `hash_fn(seed)` must
+    /// reproduce the stored hash and that hash must select the register. This
+    /// is the exhaustive counterpart of
+    /// [`HyperLogLog::probabilistic_verify`], for rejecting forged
+    /// submissions before accepting them.
+    ///
+    /// Returns the first failing register as a [`VerifyError`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, VerifyError};
+    ///
+    /// let hash_fn = |seed: u64| seed.wrapping_mul(0x9e3779b97f4a7c15);
+    /// let mut hll = HyperLogLog::new(4);
+    /// for seed in 1..50u64 {
+    ///     hll.add(seed, hash_fn(seed));
+    /// }
+    /// assert_eq!(hll.verify_work(hash_fn), Ok(()));
+    ///
+    /// let forged = HyperLogLog::from_parts(4, vec![0x10; 16], Some(vec![1; 16])).unwrap();
+    /// assert!(matches!(
+    ///     forged.verify_work(hash_fn),
+    ///     Err(VerifyError::HashMismatch { register: 0, .. })
+    /// ));
+    /// ```
+    pub fn verify_work<F: Fn(u64) -> u64>(&self, hash_fn: F) -> Result<(), VerifyError> {
+        let mask = self.register_mask();
+        for (register, stored, seed) in self.iter() {
+            if stored == u64::MAX {
+                continue;
+            }
+
+            let computed = hash_fn(seed);
+            if computed != stored {
+                return Err(VerifyError::HashMismatch {
+                    register,
+                    seed,
+                    stored,
+                    computed,
+                });
+            }
+            if (stored as usize) & mask != register {
+                return Err(VerifyError::WrongRegister {
+                    register,
+                    hash: stored,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a filled register's seed reproduces its hash and that the
+    /// hash belongs in that register.
+    fn register_verifies(&self, index: usize, hash_fn: impl Fn(u64) -> u64) -> bool {
+        let mask = self.register_mask();
+        let hash = self.hashes[index];
+        hash_fn(self.seed(index)) == hash && (hash as usize) & mask == index
+    }
+
+    /// Compute `rho` for a register's minimum hash.
+    ///
+    /// Removes the `bits` register selection bits and counts leading zeros in
+    /// the remaining `64 - bits` bits, 1-indexed. The result lies in
+    /// `1..=65 - bits`. `bits` is clamped like [`HyperLogLog::new`].
+    ///
+    /// This is the single definition of `rho` used by [`count`](Self::count)
+    /// and the register accounting. Callers should handle empty registers
+    /// (`u64::MAX`) themselves since those have no first 1-bit to find.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// // Top bit set: no leading zeros
+    /// assert_eq!(HyperLogLog::rho(12, 1 << 63), 1);
+    /// // Only register bits set: every remaining bit is zero
+    /// assert_eq!(HyperLogLog::rho(12, 0xABC), 53);
+    /// ```
+    pub fn rho(bits: u8, hash: u64) -> u32 {
+        let bits = Self::normalize_bits(bits);
+        // Remove bits used for register selection
+        let remaining = hash >> bits;
+        // Count leading zeros in remaining bits, add 1 for 1-indexed rho
+        // Use saturating_sub to prevent underflow
+        remaining.leading_zeros().saturating_sub(bits as u32) + 1
+    }
+
+    /// [`rho`](Self::rho) at this sketch's width.
+    fn register_rho(&self, hash: u64) -> u32 {
+        Self::rho(self.bits, hash)
+    }
+
+    /// Fill ratio of each contiguous group of registers.
+    ///
+    /// Registers are grouped by the high `group_bits` of their index, giving
+    /// `2^group_bits` groups of equal size. Each entry is the fraction of that
+    /// group's registers holding a hash. A well-mixed hash function fills all
+    /// groups evenly, so a lopsided result points at regional bias in the
+    /// hashing. `group_bits` is clamped to the sketch's own `bits`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add_hash(0x0); // register 0, first half
+    /// hll.add_hash(0x1); // register 1, first half
+    ///
+    /// assert_eq!(hll.occupancy_by_group(1), vec![0.25, 0.0]);
+    /// ```
+    pub fn occupancy_by_group(&self, group_bits: u8) -> Vec<f64> {
+        let group_bits = group_bits.min(self.bits);
+        let group_size = 1usize << (self.bits - group_bits);
+
+        self.hashes
+            .chunks(group_size)
+            .map(|group| {
+                let filled = group.iter().filter(|&&hash| hash != u64::MAX).count();
+                filled as f64 / group_size as f64
+            })
+            .collect()
+    }
+
+    /// Produce a human-readable, multi-line summary of the sketch state.
+    ///
+    /// The report lists the register count, occupancy, the min/max/mean `rho`
+    /// over filled registers, the deepest few registers with their seeds, and
+    /// the current estimate. It is meant for logs and `--verbose` output; the
+    /// format is not stable and should not be parsed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(42, 0x0000_0000_0000_1234);
+    ///
+    /// let report = hll.debug_dump();
+    /// assert!(report.contains("occupancy: 1/16"));
+    /// assert!(report.contains("seed=42"));
+    /// ```
+    pub fn debug_dump(&self) -> String {
+        const DEEPEST_SHOWN: usize = 5;
+
+        let mut filled: Vec<(usize, u64, u64, u32)> = self
+            .iter()
+            .filter(|&(_, hash, _)| hash != u64::MAX)
+            .map(|(index, hash, seed)| (index, hash, seed, self.register_rho(hash)))
+            .collect();
+
+        let mut report = format!(
+            "HyperLogLog bits={} registers={}\noccupancy: {}/{}\n",
+            self.bits,
+            self.hashes.len(),
+            filled.len(),
+            self.hashes.len()
+        );
+
+        if filled.is_empty() {
+            report.push_str("rho: n/a\n");
+        } else {
+            let min_rho = filled.iter().map(|r| r.3).min().unwrap_or(0);
+            let max_rho = filled.iter().map(|r| r.3).max().unwrap_or(0);
+            let mean_rho = filled.iter().map(|r| r.3 as f64).sum::<f64>() / filled.len() as f64;
+            report.push_str(&format!(
+                "rho: min={} max={} mean={:.2}\n",
+                min_rho, max_rho, mean_rho
+            ));
+
+            // Deepest first; ties broken by register index for stable output
+            filled.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+            report.push_str("deepest registers:\n");
+            for (index, hash, seed, rho) in filled.iter().take(DEEPEST_SHOWN) {
+                report.push_str(&format!(
+                    "  [{}] hash={:#018x} seed={} rho={}\n",
+                    index, hash, seed, rho
+                ));
+            }
+        }
+
+        report.push_str(&format!("estimate: {:.2}", self.count()));
+        report
+    }
+
+    /// Get the number of bits used for register selection.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Width of the stored hashes in bits (64).
+    pub fn hash_bit_width(&self) -> u32 {
+        u64::BITS
+    }
+
+    /// Deepest `rho` a register can reach: `hash_bit_width - bits + 1`.
+    ///
+    /// Reached by a hash whose bits above the register selection bits are all
+    /// zero. See [`HyperLogLog::rho`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hll = HyperLogLog::new(12);
+    /// assert_eq!(hll.max_rho(), 53);
+    /// assert_eq!(HyperLogLog::rho(12, 0), hll.max_rho());
+    /// ```
+    pub fn max_rho(&self) -> u32 {
+        self.hash_bit_width() - self.bits as u32 + 1
+    }
+
+    /// Number of registers holding a hash, out of `2^bits`.
+    ///
+    /// A more direct progress indicator than [`HyperLogLog::count`]. Uses the
+    /// running occupancy, so this is O(1) unless
+    /// [`HyperLogLog::hashes_mut`] was used since the last mutation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add_hash(0x10);
+    /// hll.add_hash(0x20); // same register
+    /// hll.add_hash(0x03);
+    /// assert_eq!(hll.filled_registers(), 2);
+    /// ```
+    pub fn filled_registers(&self) -> usize {
+        self.current_accounting().1
+    }
+
+    /// Whether no register holds a hash yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// assert!(hll.is_empty());
+    /// hll.add_hash(0x1);
+    /// assert!(!hll.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.filled_registers() == 0
+    }
+
+    /// Get a reference to the minimum hashes array.
+    ///
+    /// Each element is the minimum hash seen for that register,
+    /// or `u64::MAX` if no hash has been added to that register.
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// Whether two sketches hold the same register hashes, ignoring seeds.
+    ///
+    /// `==` compares the bits, the hashes and every register's seed, so it
+    /// also tells apart sketches that differ only in their proofs, or in
+    /// stray seeds left on empty registers (see
+    /// [`HyperLogLog::canonicalize`]). This compares only the hashes, which
+    /// is all the cardinality state there is: sketches that are
+    /// `same_hashes` give identical estimates under the same
+    /// [`BiasTable`]. Use it to compare against a
+    /// copy that lost its seeds, such as one read back with
+    /// [`HyperLogLog::from_json`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(42, 0x1234);
+    ///
+    /// let restored = HyperLogLog::from_json(4, &hll.to_json());
+    /// assert_ne!(restored, hll); // seed 42 didn't survive
+    /// assert!(restored.same_hashes(&hll));
+    /// ```
+    pub fn same_hashes(&self, other: &Self) -> bool {
+        self.hashes == other.hashes
+    }
+
+    /// Get a mutable reference to the minimum hashes array.
+    ///
+    /// This allows direct manipulation of register values, useful when
+    /// reconstructing HLL state from storage. The cached estimator accounting
+    /// is invalidated and rebuilt on the next mutation.
+    pub fn hashes_mut(&mut self) -> &mut [u64] {
+        self.accounting_fresh = false;
+        &mut self.hashes
+    }
+
+    /// Iterate over every register as `(index, hash, seed)`.
+    ///
+    /// Empty registers are included, holding `u64::MAX` and seed 0, so the
+    /// iterator always yields exactly `2^bits` items in index order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(2);
+    /// hll.add(42, 0x1);
+    ///
+    /// let registers: Vec<_> = hll.iter().collect();
+    /// assert_eq!(registers.len(), 4);
+    /// assert_eq!(registers[1], (1, 0x1, 42));
+    /// assert_eq!(registers[0], (0, u64::MAX, 0));
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u64, u64)> + '_ {
+        self.hashes
+            .iter()
+            .enumerate()
+            .map(|(index, &hash)| (index, hash, self.seed(index)))
+    }
+
+    /// Iterate over every register as a named [`Register`].
+    ///
+    /// Yields the same `2^bits` entries as [`HyperLogLog::iter`], with empty
+    /// registers flagged by [`Register::is_empty`] rather than skipped, so a
+    /// visualization can lay them out by index without reaching for the
+    /// parallel [`HyperLogLog::hashes`] and [`HyperLogLog::seeds`] slices.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, Register};
+    ///
+    /// let mut hll = HyperLogLog::new(2);
+    /// hll.add(42, 0x1);
+    ///
+    /// let filled: Vec<Register> = hll.registers().filter(|r| !r.is_empty()).collect();
+    /// assert_eq!(
+    ///     filled,
+    ///     [Register {
+    ///         index: 1,
+    ///         hash: 0x1,
+    ///         seed: 42
+    ///     }]
+    /// );
+    /// ```
+    pub fn registers(&self) -> impl Iterator<Item = Register> + '_ {
+        self.iter()
+            .map(|(index, hash, seed)| Register { index, hash, seed })
+    }
+
+    /// The `k` filled registers holding the hardest-to-find hashes, hardest
+    /// first.
+    ///
+    /// Difficulty is the depth of the hash above the register selection
+    /// bits: more leading zeros ([`HyperLogLog::rho`]) is harder, and within
+    /// the same count a lower hash is harder still. Ties fall back to the
+    /// register index. Returns fewer than `k` entries if fewer registers are
+    /// filled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(1, 0x0000_0000_0000_1001); // 47 leading zeros above the register bits
+    /// hll.add(2, 0x00F0_0000_0000_0002); // 4
+    /// hll.add(3, 0x0000_F000_0000_0003); // 12
+    ///
+    /// let top: Vec<u64> = hll.top_k_registers(2).iter().map(|r| r.seed).collect();
+    /// assert_eq!(top, [1, 3]);
+    /// ```
+    pub fn top_k_registers(&self, k: usize) -> Vec<Register> {
+        let depth = |register: &Register| (register.hash >> self.bits, register.index);
+        let mut filled: Vec<Register> = self.registers().filter(|r| !r.is_empty()).collect();
+
+        if k < filled.len() {
+            filled.select_nth_unstable_by_key(k, depth);
+            filled.truncate(k);
+        }
+        filled.sort_unstable_by_key(depth);
+        filled
+    }
+
+    /// Get the seeds array.
+    ///
+    /// Each element is the seed that produced the minimum hash for that
+    /// register. Seeds are 0 for registers that haven't been updated or
+    /// where `add_hash` was used instead of `add`. Sketches built without
+    /// seeds (see [`HyperLogLogBuilder::with_seeds`]) return an empty slice.
+    ///
+    /// Sketches from [`HyperLogLog::new_lazy_seeds`] don't store this array.
+    /// The first call after each change allocates and fills it with `2^bits`
+    /// seeds, and later calls reuse it until the sketch changes again;
+    /// prefer [`HyperLogLog::seed`] for single lookups.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new_lazy_seeds(4);
+    /// hll.add(7, 0x0000_0000_0000_0013);
+    ///
+    /// assert_eq!(hll.seeds().len(), 16);
+    /// assert_eq!(hll.seeds()[3], 7);
+    /// ```
+    pub fn seeds(&self) -> &[u64] {
+        match &self.seeds {
+            Seeds::Dense(seeds) => seeds,
+            Seeds::Lazy(seeds) => seeds.materialize(self.hashes.len()),
+            Seeds::Disabled => &[],
+        }
+    }
+
+    /// Get the seed that produced the minimum hash for `register`.
+    ///
+    /// Returns 0 if no seed was recorded. Panics if `register` is out of
+    /// range, like indexing [`HyperLogLog::hashes`].
+    pub fn seed(&self, register: usize) -> u64 {
+        assert!(
+            register < self.hashes.len(),
+            "register {} out of range",
+            register
+        );
+        self.seeds.get(register)
+    }
+
+    /// Create a HyperLogLog from a JSON string.
+    ///
+    /// The JSON should be an array of string-encoded u64 values (to avoid
+    /// JavaScript number precision issues). Seeds are not restored from JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Number of bits for register selection
+    /// * `json` - JSON array of string-encoded hash values
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let json = r#"["18446744073709551615", "12345678901234567890"]"#;
+    /// let hll = HyperLogLog::from_json(1, json); // 2 registers for bits=1
+    /// ```
+    ///
+    /// This is lenient: input that [`HyperLogLog::from_json_checked`] would
+    /// reject still yields a sketch. Invalid JSON gives an empty sketch,
+    /// extra values are ignored, missing ones leave registers empty, and
+    /// values that don't parse as `u64` are skipped.
+    pub fn from_json(bits: u8, json: &str) -> Self {
+        Self::read_json_array(bits, json)
+            .map(|(state, _)| state)
+            .unwrap_or_else(|_| Self::new(bits))
+    }
+
+    /// Create a HyperLogLog from a JSON string, rejecting any mismatch.
+    ///
+    /// Reads the same array as [`HyperLogLog::from_json`], with `bits`
+    /// clamped like [`HyperLogLog::new`], but the array must then hold
+    /// exactly `2^bits` values, each a string-encoded `u64`. Use this when
+    /// `bits` comes from somewhere other than the JSON itself, so a mismatch
+    /// surfaces instead of producing a truncated or short sketch.
+    ///
+    /// Returns [`ParseError::InvalidJson`] if the input isn't an array of
+    /// strings, [`ParseError::LengthMismatch`] if it has the wrong number of
+    /// values, and [`ParseError::InvalidRegister`] for the first value that
+    /// isn't a `u64`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, ParseError};
+    ///
+    /// let json = r#"["18446744073709551615", "12345678901234567890"]"#;
+    /// let hll = HyperLogLog::from_json_checked(1, json).unwrap();
+    /// assert_eq!(hll.hashes(), &[u64::MAX, 12345678901234567890]);
+    ///
+    /// assert_eq!(
+    ///     HyperLogLog::from_json_checked(2, json),
+    ///     Err(ParseError::LengthMismatch { expected: 4, found: 2 })
+    /// );
+    /// ```
+    pub fn from_json_checked(bits: u8, json: &str) -> Result<Self, ParseError> {
+        let (state, array) =
+            Self::read_json_array(bits, json).map_err(|_| ParseError::InvalidJson)?;
+        let expected = state.hashes.len();
+        if array.found != expected {
+            return Err(ParseError::LengthMismatch {
+                expected,
+                found: array.found,
+            });
+        }
+        if let Some(register) = array.first_invalid {
+            return Err(ParseError::InvalidRegister { register });
+        }
+
+        Ok(state)
+    }
+
+    /// Stream a bare JSON register array into a new sketch at `bits`.
+    ///
+    /// Each value is parsed straight into its register rather than
+    /// collected as a `String` first. Extra values are counted but ignored,
+    /// and values that aren't a `u64` leave their register empty.
+    fn read_json_array(
+        bits: u8,
+        json: &str,
+    ) -> Result<(Self, json_stream::RegisterArray), serde_json::Error> {
+        let mut state = Self::new(bits);
+        let array = json_stream::read_registers(json, &mut state.hashes)?;
+        state.accounting_fresh = false;
+        state.sync_accounting();
+        Ok((state, array))
+    }
+
+    /// Overwrite registers from string-encoded hashes, leaving unparseable
+    /// entries empty.
+    fn load_registers<'a>(&mut self, values: impl Iterator<Item = &'a str>) {
+        for (index, value) in values.enumerate().take(self.hashes.len()) {
+            if let Ok(parsed_value) = value.parse::<u64>() {
+                self.hashes[index] = parsed_value;
+            }
+        }
+        self.accounting_fresh = false;
+        self.sync_accounting();
+    }
+
+    /// Create a HyperLogLog from versioned JSON, falling back to the legacy format.
+    ///
+    /// Input starting with `[` is treated as the bare array written by
+    /// [`HyperLogLog::to_json`] and read at `bits`. Anything else must be the
+    /// envelope written by [`HyperLogLog::to_json_versioned`], whose own `bits`
+    /// has to match.
+    ///
+    /// Unlike [`HyperLogLog::from_json`], malformed input is an error rather
+    /// than an empty sketch. Individual register values that don't parse as
+    /// `u64` are still left empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add_hash(0x1234);
+    ///
+    /// let restored = HyperLogLog::from_json_versioned(4, &hll.to_json_versioned()).unwrap();
+    /// assert_eq!(restored, hll);
+    ///
+    /// // Legacy bare arrays are still accepted
+    /// let legacy = HyperLogLog::from_json_versioned(4, &hll.to_json()).unwrap();
+    /// assert_eq!(legacy.hashes(), hll.hashes());
+    /// ```
+    pub fn from_json_versioned(bits: u8, json: &str) -> Result<Self, ParseError> {
+        let bits = Self::normalize_bits(bits);
+
+        if json.trim_start().starts_with('[') {
+            return Self::read_json_array(bits, json)
+                .map(|(state, _)| state)
+                .map_err(|_| ParseError::InvalidJson);
+        }
+
+        let envelope =
+            serde_json::from_str::<serde_json::Value>(json).map_err(|_| ParseError::InvalidJson)?;
+
+        let version = envelope
+            .get("v")
+            .and_then(|value| value.as_u64())
+            .ok_or(ParseError::InvalidJson)?;
+        if version != JSON_FORMAT_VERSION {
+            return Err(ParseError::UnsupportedVersion(version));
+        }
+
+        let found = envelope
+            .get("bits")
+            .and_then(|value| value.as_u64())
+            .ok_or(ParseError::InvalidJson)?;
+        if found != bits as u64 {
+            return Err(ParseError::BitsMismatch {
+                expected: bits,
+                found: u8::try_from(found).unwrap_or(u8::MAX),
+            });
+        }
+
+        let values = envelope
+            .get("hashes")
+            .and_then(|value| value.as_array())
+            .ok_or(ParseError::InvalidJson)?
+            .iter()
+            .map(|value| value.as_str().ok_or(ParseError::InvalidJson))
+            .collect::<Result<Vec<&str>, ParseError>>()?;
+
+        let mut state = Self::new(bits);
+        state.load_registers(values.into_iter());
+        Ok(state)
+    }
+
+    /// Estimate the Jaccard similarity of the sets behind two sketches.
+    ///
+    /// Each register holds the minimum hash of its share of the set, so it
+    /// matches across both sketches exactly when that minimum over the union
+    /// came from the intersection. The fraction of registers filled in either
+    /// sketch that hold the same minimum hash therefore estimates
+    /// `|A ∩ B| / |A ∪ B|`. Two empty sketches are considered identical
+    /// (`1.0`).
+    ///
+    /// Returns [`MergeError::BitsMismatch`] if the sketches use a different
+    /// number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, SplitMix64};
+    ///
+    /// let mut a = HyperLogLog::new(10);
+    /// let mut b = HyperLogLog::new(10);
+    /// for seed in 0..2000 {
+    ///     a.add_seed(seed, &SplitMix64);
+    ///     b.add_seed(seed + 10_000, &SplitMix64);
+    /// }
+    ///
+    /// assert_eq!(a.jaccard_similarity(&a).unwrap(), 1.0);
+    /// assert_eq!(a.jaccard_similarity(&b).unwrap(), 0.0);
+    /// ```
+    pub fn jaccard_similarity(&self, other: &HyperLogLog) -> Result<f64, MergeError> {
+        self.check_compatible(other)?;
+
+        Ok(self.jaccard_registers(other.hashes.iter().copied()))
+    }
+
+    /// Estimate how many items the sets behind two sketches share.
+    ///
+    /// Uses inclusion-exclusion, `count(a) + count(b) - count(a ∪ b)`, with
+    /// the union built by [`HyperLogLog::union`], clamped at zero.
+    ///
+    /// # Error Amplification
+    ///
+    /// The three estimates each carry a standard error of about
+    /// [`HyperLogLog::relative_error`] times their own size, and those
+    /// absolute errors add up in the difference. The absolute error therefore
+    /// scales with the union, not the intersection: with 2^12 registers and
+    /// two sets of a million items sharing 10,000, the error is tens of
+    /// thousands of items, several times the true answer. Treat small
+    /// intersections relative to the union as indistinguishable from zero,
+    /// and prefer [`HyperLogLog::jaccard_similarity`] times the union when the
+    /// overlap fraction is what matters.
+    ///
+    /// Returns [`MergeError::BitsMismatch`] if the sketches use a different
+    /// number of bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, SplitMix64};
+    ///
+    /// let mut a = HyperLogLog::new(12);
+    /// let mut b = HyperLogLog::new(12);
+    /// for seed in 0..20_000 {
+    ///     a.add_seed(seed, &SplitMix64);
+    ///     b.add_seed(seed + 10_000, &SplitMix64);
+    /// }
+    ///
+    /// let shared = a.intersection_estimate(&b).unwrap();
+    /// assert!(shared > 5_000.0 && shared < 15_000.0);
+    /// ```
+    pub fn intersection_estimate(&self, other: &HyperLogLog) -> Result<f64, MergeError> {
+        let union = Self::union(self, other)?;
+        Ok((self.count() + other.count() - union.count()).max(0.0))
+    }
+
+    /// Estimate the Jaccard similarity with a JSON-encoded sketch.
+    ///
+    /// The JSON is read the same way as [`HyperLogLog::from_json`], streaming
+    /// its registers into a plain buffer instead of materializing a second
+    /// `HyperLogLog`. Similarity is the number of
+    /// registers where both sketches hold the same minimum hash, divided by
+    /// the number of registers filled in either. Two empty sketches are
+    /// considered identical (`1.0`).
+    ///
+    /// Returns an error if `bits` differs from this sketch or the JSON is not
+    /// an array of strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add_hash(0x1234);
+    ///
+    /// let similarity = hll.jaccard_with_json(4, &hll.to_json()).unwrap();
+    /// assert_eq!(similarity, 1.0);
+    /// ```
+    pub fn jaccard_with_json(&self, bits: u8, json: &str) -> Result<f64, ParseError> {
+        let bits = Self::normalize_bits(bits);
+        if bits != self.bits {
+            return Err(ParseError::BitsMismatch {
+                expected: self.bits,
+                found: bits,
+            });
+        }
+
+        let mut other = vec![u64::MAX; self.hashes.len()];
+        json_stream::read_registers(json, &mut other).map_err(|_| ParseError::InvalidJson)?;
+
+        Ok(self.jaccard_registers(other.into_iter()))
+    }
+
+    /// Min-hash Jaccard estimate against another register sequence.
+    fn jaccard_registers(&self, other: impl Iterator<Item = u64>) -> f64 {
+        let mut union = 0usize;
+        let mut shared = 0usize;
+
+        for (&mine, theirs) in self.hashes.iter().zip(other) {
+            if mine == u64::MAX && theirs == u64::MAX {
+                continue;
+            }
+            union += 1;
+            if mine == theirs {
+                shared += 1;
+            }
+        }
+
+        if union == 0 {
+            1.0
+        } else {
+            shared as f64 / union as f64
+        }
+    }
+
+    /// Serialize the hashes to a JSON string.
+    ///
+    /// Hashes are encoded as strings to avoid JavaScript number precision
+    /// issues with large u64 values. Seeds are not included in the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4); // 16 registers
+    /// hll.add_hash(0x1234);
+    ///
+    /// let json = hll.to_json();
+    /// assert!(json.starts_with('['));
+    /// assert!(json.ends_with(']'));
+    /// ```
+    pub fn to_json(&self) -> String {
+        let values: Vec<String> = self.hashes.iter().map(|value| value.to_string()).collect();
+        serde_json::to_string(&values).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Add one hash to a sketch stored as [`HyperLogLog::to_json`] output,
+    /// rewriting only the target register's string.
+    ///
+    /// Returns whether the register improved and the updated JSON, which is
+    /// exactly what [`HyperLogLog::from_json`], [`HyperLogLog::add_hash`] and
+    /// [`HyperLogLog::to_json`] would produce. When `json` is in the layout
+    /// `to_json` writes, the sketch is never materialized: one scan locates
+    /// the register and one string is built. Any other input takes that full
+    /// cycle instead, so the result is the same either way.
+    ///
+    /// JSON carries no seeds, so none is taken here. Returns
+    /// [`ParseError::RegisterMismatch`] if `hash` doesn't select `register`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let json = HyperLogLog::new(2).to_json();
+    /// let (improved, json) = HyperLogLog::apply_single_to_json(&json, 2, 1, 0x15).unwrap();
+    /// assert!(improved);
+    ///
+    /// let mut hll = HyperLogLog::new(2);
+    /// hll.add_hash(0x15);
+    /// assert_eq!(json, hll.to_json());
+    /// ```
+    pub fn apply_single_to_json(
+        json: &str,
+        bits: u8,
+        register: usize,
+        hash: u64,
+    ) -> Result<(bool, String), ParseError> {
+        use std::fmt::Write as _;
+
+        let bits = Self::normalize_bits(bits);
+        let m = 1usize << bits;
+        if register != (hash as usize) & (m - 1) {
+            return Err(ParseError::RegisterMismatch { register, hash });
+        }
+
+        let Some((start, end, current)) = Self::canonical_json_register(json, m, register) else {
+            let mut state = Self::from_json(bits, json);
+            let improved = state.add_hash(hash);
+            return Ok((improved, state.to_json()));
+        };
+
+        if hash >= current {
+            return Ok((false, json.to_string()));
+        }
+
+        let mut updated = String::with_capacity(json.len() + 20);
+        updated.push_str(&json[..start]);
+        let _ = write!(updated, "{}", hash);
+        updated.push_str(&json[end..]);
+        Ok((true, updated))
+    }
+
+    /// Locate `register` in JSON laid out exactly as [`HyperLogLog::to_json`]
+    /// writes `m` registers, returning the byte span of its digits and its
+    /// value, or `None` if the input deviates from that layout anywhere.
+    fn canonical_json_register(
+        json: &str,
+        m: usize,
+        register: usize,
+    ) -> Option<(usize, usize, u64)> {
+        let bytes = json.as_bytes();
+        if bytes.first() != Some(&b'[') {
+            return None;
+        }
+
+        let mut pos = 1;
+        let mut target = None;
+        for index in 0..m {
+            if index > 0 {
+                if bytes.get(pos) != Some(&b',') {
+                    return None;
+                }
+                pos += 1;
+            }
+            if bytes.get(pos) != Some(&b'"') {
+                return None;
+            }
+            pos += 1;
+
+            let start = pos;
+            while bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+                pos += 1;
+            }
+            let digits = &json[start..pos];
+            if bytes.get(pos) != Some(&b'"') || (digits.len() > 1 && digits.starts_with('0')) {
+                return None;
+            }
+            let value = digits.parse::<u64>().ok()?;
+            pos += 1;
+
+            if index == register {
+                target = Some((start, pos - 1, value));
+            }
+        }
+
+        if pos + 1 != bytes.len() || bytes[pos] != b']' {
+            return None;
+        }
+        target
+    }
+
+    /// Stream the hashes as JSON to a writer.
+    ///
+    /// Produces exactly the output of [`HyperLogLog::to_json`], but formats
+    /// each hash straight into `writer` instead of building the whole string
+    /// first, so large sketches can go directly to a response body or file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(2);
+    /// hll.add_hash(0x1);
+    ///
+    /// let mut out = Vec::new();
+    /// hll.write_json(&mut out).unwrap();
+    /// assert_eq!(String::from_utf8(out).unwrap(), hll.to_json());
+    /// ```
+    pub fn write_json<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(b"[")?;
+        for (index, hash) in self.hashes.iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(writer, "\"{}\"", hash)?;
+        }
+        writer.write_all(b"]")
+    }
+
+    /// Serialize the hashes to a versioned JSON envelope.
+    ///
+    /// The output looks like `{"v":1,"bits":12,"hashes":[...]}`, with hashes
+    /// string-encoded as in [`HyperLogLog::to_json`]. Recording the format
+    /// version and width lets readers reject data they can't interpret instead
+    /// of misreading it. Seeds are not included in version 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hll = HyperLogLog::new(1);
+    /// assert_eq!(
+    ///     hll.to_json_versioned(),
+    ///     r#"{"v":1,"bits":1,"hashes":["18446744073709551615","18446744073709551615"]}"#
+    /// );
+    /// ```
+    pub fn to_json_versioned(&self) -> String {
+        format!(
+            r#"{{"v":{},"bits":{},"hashes":{}}}"#,
+            JSON_FORMAT_VERSION,
+            self.bits,
+            self.to_json()
+        )
+    }
+
+    /// Serialize to a compact binary layout.
+    ///
+    /// The layout is one byte for `bits`, one flag byte (`1` when seeds
+    /// follow), then `2^bits` little-endian `u64` hashes and, if flagged, the
+    /// same number of little-endian `u64` seeds. Empty registers are written
+    /// as `u64::MAX` so the length depends only on `bits`. This writer always
+    /// includes seeds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let hll = HyperLogLog::new(4);
+    /// assert_eq!(hll.to_bytes().len(), 2 + 16 * 8 * 2);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BYTES_HEADER_LEN + self.hashes.len() * 16);
+        bytes.push(self.bits);
+        bytes.push(BYTES_FLAG_SEEDS);
+        for hash in &self.hashes {
+            bytes.extend_from_slice(&hash.to_le_bytes());
+        }
+        for register in 0..self.hashes.len() {
+            bytes.extend_from_slice(&self.seed(register).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize the layout written by [`HyperLogLog::to_bytes`].
+    ///
+    /// Input without the seeds flag restores seeds as 0. Returns
+    /// [`ParseError::InvalidBytes`] if `bits` is out of range, the flag is
+    /// unknown, or the length doesn't match the header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::HyperLogLog;
+    ///
+    /// let mut hll = HyperLogLog::new(4);
+    /// hll.add(7, 0x1234);
+    ///
+    /// let restored = HyperLogLog::from_bytes(&hll.to_bytes()).unwrap();
+    /// assert_eq!(restored, hll);
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        let (bits, with_seeds) = Self::bytes_header(data)?;
+
+        let mut state = Self::new(bits);
+        let mut words = data[BYTES_HEADER_LEN..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap_or([0xFF; 8])));
+        for hash in state.hashes.iter_mut() {
+            *hash = words.next().unwrap_or(u64::MAX);
+        }
+        if with_seeds {
+            for register in 0..state.hashes.len() {
+                state.seeds.set(register, words.next().unwrap_or(0));
+            }
+        }
+
+        state.accounting_fresh = false;
+        state.sync_accounting();
+        Ok(state)
+    }
+
+    /// Validate a binary header against the data length, returning `bits`
+    /// and whether seeds follow the hashes.
+    fn bytes_header(data: &[u8]) -> Result<(u8, bool), ParseError> {
+        let (&bits, rest) = data.split_first().ok_or(ParseError::InvalidBytes)?;
+        let (&flag, body) = rest.split_first().ok_or(ParseError::InvalidBytes)?;
+        if bits == 0 || bits > MAX_HLL_BITS {
+            return Err(ParseError::InvalidBytes);
+        }
+
+        let with_seeds = match flag {
+            0 => false,
+            BYTES_FLAG_SEEDS => true,
+            _ => return Err(ParseError::InvalidBytes),
+        };
+
+        let words = (1usize << bits) * if with_seeds { 2 } else { 1 };
+        if body.len() != words * 8 {
+            return Err(ParseError::InvalidBytes);
+        }
+
+        Ok((bits, with_seeds))
+    }
+
+    /// Merge a JSON upload into a binary sketch without building either as a struct.
+    ///
+    /// `stored` is the [`HyperLogLog::to_bytes`] layout and `json` the
+    /// [`HyperLogLog::to_json`] layout, both at `bits`. Each register keeps
+    /// the lower hash; registers taken from the JSON get seed 0 since JSON
+    /// carries no seeds. The result matches merging the decoded sketches and
+    /// re-encoding, for servers moving storage to binary while still
+    /// accepting JSON uploads.
+    ///
+    /// Returns [`ParseError::BitsMismatch`] if the stored sketch uses a
+    /// different width, [`ParseError::InvalidBytes`] if it is malformed, and
+    /// otherwise rejects the JSON like [`HyperLogLog::from_json_checked`]:
+    /// it must hold exactly `2^bits` string-encoded `u64` values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperloglog::{HyperLogLog, ParseError};
+    ///
+    /// let stored = HyperLogLog::new(1).to_bytes();
+    /// let json = r#"["100", "18446744073709551615"]"#;
+    /// let merged = HyperLogLog::merge_json_into_bytes(&stored, 1, json).unwrap();
+    /// assert_eq!(HyperLogLog::from_bytes(&merged).unwrap().hashes()[0], 100);
+    ///
+    /// assert_eq!(
+    ///     HyperLogLog::merge_json_into_bytes(&stored, 1, r#"["100"]"#),
+    ///     Err(ParseError::LengthMismatch { expected: 2, found: 1 })
+    /// );
+    /// ```
+    pub fn merge_json_into_bytes(
+        stored: &[u8],
+        bits: u8,
+        json: &str,
+    ) -> Result<Vec<u8>, ParseError> {
+        let bits = Self::normalize_bits(bits);
+        let (found, with_seeds) = Self::bytes_header(stored)?;
+        if found != bits {
+            return Err(ParseError::BitsMismatch {
+                expected: bits,
+                found,
+            });
+        }
+
+        let m = 1usize << bits;
+        let mut uploaded = vec![u64::MAX; m];
+        let array = json_stream::read_registers(json, &mut uploaded)
+            .map_err(|_| ParseError::InvalidJson)?;
+        if array.found != m {
+            return Err(ParseError::LengthMismatch {
+                expected: m,
+                found: array.found,
+            });
+        }
+        if let Some(register) = array.first_invalid {
+            return Err(ParseError::InvalidRegister { register });
+        }
+
+        let mut merged = stored.to_vec();
+        for (index, hash) in uploaded.into_iter().enumerate() {
+            let offset = BYTES_HEADER_LEN + index * 8;
+            let slot = &mut merged[offset..offset + 8];
+            if hash < u64::from_le_bytes(slot.try_into().unwrap_or([0xFF; 8])) {
+                slot.copy_from_slice(&hash.to_le_bytes());
+                if with_seeds {
+                    let seed_offset = offset + m * 8;
+                    merged[seed_offset..seed_offset + 8].copy_from_slice(&0u64.to_le_bytes());
+                }
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+impl Default for HyperLogLog {
+    /// An empty sketch with [`DEFAULT_HLL_BITS`] and seeds recorded.
+    fn default() -> Self {
+        Self::new(DEFAULT_HLL_BITS)
+    }
+}
+
+/// Configures a [`HyperLogLog`]; created by [`HyperLogLog::builder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HyperLogLogBuilder {
+    bits: u8,
+    with_seeds: bool,
+}
+
+impl Default for HyperLogLogBuilder {
+    fn default() -> Self {
+        Self {
+            bits: DEFAULT_HLL_BITS,
+            with_seeds: true,
+        }
+    }
+}
+
+impl HyperLogLogBuilder {
+    /// Use `2^bits` registers, clamped like [`HyperLogLog::new`].
+    pub fn bits(mut self, bits: u8) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// Whether to record the seed behind each register.
+    ///
+    /// Without seeds the sketch still estimates cardinality but proves no
+    /// work: [`HyperLogLog::add`] ignores its seed argument,
+    /// [`HyperLogLog::seed`] returns 0 and [`HyperLogLog::seeds`] is empty.
+    /// This saves a `u64` per register, e.g. for a browser runner that only
+    /// reports counts.
+    pub fn with_seeds(mut self, with_seeds: bool) -> Self {
+        self.with_seeds = with_seeds;
+        self
+    }
+
+    /// Create the empty sketch.
+    pub fn build(self) -> HyperLogLog {
+        let mut hll = HyperLogLog::new(self.bits);
+        if !self.with_seeds {
+            hll.seeds = Seeds::Disabled;
+        }
+        hll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simple splitmix64 hash for testing
+    fn splitmix(mut x: u64) -> u64 {
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    #[test]
+    fn test_new_creates_correct_size() {
+        let hll = HyperLogLog::new(4);
+        assert_eq!(hll.bits(), 4);
+        assert_eq!(hll.hashes().len(), 16);
+        assert_eq!(hll.seeds().len(), 16);
+    }
+
+    #[test]
+    fn test_new_normalizes_bits() {
+        // Too low - clamped to 1
+        let hll = HyperLogLog::new(0);
+        assert_eq!(hll.bits(), 1);
+        assert_eq!(hll.hashes().len(), 2);
+
+        // Too high - clamped to MAX_HLL_BITS
+        let hll = HyperLogLog::new(30);
+        assert_eq!(hll.bits(), MAX_HLL_BITS);
+        assert_eq!(hll.hashes().len(), 1 << MAX_HLL_BITS);
+    }
+
+    #[test]
+    fn test_huge_bits_clamp_instead_of_overflowing() {
+        let mut hll = HyperLogLog::new(200);
+        assert_eq!(hll.bits(), MAX_HLL_BITS);
+        assert!(hll.add(1, u64::MAX - 1));
+        assert_eq!(hll.count(), hll.count_stable());
+
+        let json = HyperLogLog::new(2).to_json();
+        assert_eq!(HyperLogLog::from_json(200, &json).bits(), MAX_HLL_BITS);
+        assert_eq!(
+            HyperLogLog::from_json_checked(200, &json),
+            Err(ParseError::LengthMismatch {
+                expected: 1 << MAX_HLL_BITS,
+                found: 4
+            })
+        );
+
+        assert_eq!(
+            HyperLogLog::builder().bits(u8::MAX).build().bits(),
+            MAX_HLL_BITS
+        );
+        assert_eq!(HyperLogLog32::new(200).bits(), MAX_HLL_BITS);
+        assert_eq!(
+            HyperLogLog::relative_error_for_bits(200),
+            HyperLogLog::relative_error_for_bits(MAX_HLL_BITS)
+        );
+        assert_eq!(HyperLogLog::rho(200, 1), HyperLogLog::rho(MAX_HLL_BITS, 1));
+    }
+
+    #[test]
+    fn test_add_returns_true_on_improvement() {
+        let mut hll = HyperLogLog::new(4);
+
+        // First add always improves from u64::MAX
+        assert!(hll.add(1, 0xFFFF_0000));
+
+        // Lower hash improves
+        assert!(hll.add(2, 0x0000_0000)); // Same register (bits 0-3)
+
+        // Higher hash doesn't improve
+        assert!(!hll.add(3, 0xFFFF_0000));
+    }
+
+    #[test]
+    fn test_add_tracks_seed() {
+        let mut hll = HyperLogLog::new(4);
+
+        // With 4 bits, register selection uses lower 4 bits
+        // Hash 0x0013 -> register 3 (0x13 & 0xF = 3)
+        hll.add(42, 0x0013);
+        assert_eq!(hll.seeds()[3], 42);
+
+        // Lower hash that still maps to register 3
+        // Hash 0x0003 -> register 3 (0x03 & 0xF = 3), and 0x0003 < 0x0013
+        hll.add(99, 0x0003);
+        assert_eq!(hll.seeds()[3], 99);
+    }
+
+    #[test]
+    fn test_add_hash_sets_seed_to_zero() {
+        let mut hll = HyperLogLog::new(4);
+
+        hll.add_hash(0x0005); // Register 5
+        assert_eq!(hll.seeds()[5], 0);
+    }
+
+    #[test]
+    fn test_count_empty_returns_zero() {
+        let hll = HyperLogLog::new(12);
+        assert_eq!(hll.count(), 0.0);
+    }
+
+    #[test]
+    fn test_count_estimates_cardinality() {
+        let mut hll = HyperLogLog::new(12);
+        let n = 10_000u64;
+
+        for seed in 0..n {
+            let hash = splitmix(seed);
+            hll.add(seed, hash);
+        }
+
+        let estimate = hll.count();
+
+        // With 12 bits (4096 registers) the standard error is about 1.6%, so
+        // 5% leaves room for the raw estimator's small-range bias.
+        let lower = n as f64 * 0.95;
+        let upper = n as f64 * 1.05;
+        assert!(
+            estimate > lower && estimate < upper,
+            "Expected estimate near {}, got {}",
+            n,
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_count_handles_single_item() {
+        let mut hll = HyperLogLog::new(12);
+        let hash = splitmix(0);
+        hll.add(0, hash);
+
+        let estimate = hll.count();
+        // With min-hash HLL, a single item (especially one with few leading zeros)
+        // produces a high estimate because most registers are still at u64::MAX.
+        // The estimate decreases as we find more (and lower) hashes.
+        assert!(estimate > 0.0, "Estimate should be positive");
+    }
+
+    #[test]
+    fn test_count_lower_bound_never_exceeds_estimate() {
+        let mut hll = HyperLogLog::new(12);
+        assert_eq!(hll.count_lower_bound(), 0.0);
+
+        for seed in 0..100_000u64 {
+            hll.add(seed, splitmix(seed));
+            if seed < 20 {
+                // Very sparse: the bound is exactly the occupancy
+                assert_eq!(hll.count_lower_bound(), hll.filled_registers() as f64);
+            }
+            if seed % 997 == 0 {
+                assert!(hll.count_lower_bound() <= hll.count(), "seed {}", seed);
+            }
+        }
+        assert!(hll.count_lower_bound() <= 4096.0);
+        assert!(hll.count_lower_bound() <= hll.count());
+    }
+
+    #[test]
+    fn test_default_and_builder_with_seeds() {
+        assert_eq!(HyperLogLog::default(), HyperLogLog::new(DEFAULT_HLL_BITS));
+        assert_eq!(HyperLogLog::builder().build(), HyperLogLog::default());
+
+        let mut built = HyperLogLog::builder().bits(12).build();
+        let mut plain = HyperLogLog::new(12);
+        for seed in 0..1_000u64 {
+            assert_eq!(
+                built.add(seed, splitmix(seed)),
+                plain.add(seed, splitmix(seed))
+            );
+        }
+        assert_eq!(built, plain);
+        assert_eq!(built.seeds().len(), 4096);
+        assert_eq!(built.verify_work(splitmix), Ok(()));
+
+        // Out-of-range bits are clamped like new()
+        assert_eq!(HyperLogLog::builder().bits(0).build().bits(), 1);
+    }
+
+    #[test]
+    fn test_builder_without_seeds() {
+        let mut hll = HyperLogLog::builder().bits(8).with_seeds(false).build();
+        let mut seeded = HyperLogLog::new(8);
+        for seed in 0..2_000u64 {
+            assert_eq!(
+                hll.add(seed, splitmix(seed)),
+                seeded.add(seed, splitmix(seed))
+            );
+        }
+
+        assert_eq!(hll.hashes(), seeded.hashes());
+        assert_eq!(hll.count(), seeded.count());
+        assert!(hll.seeds().is_empty());
+        assert!((0..256).all(|register| hll.seed(register) == 0));
+        assert!(hll.registers().all(|register| register.seed == 0));
+
+        // Merging seeded work in keeps the hashes but drops the seeds
+        let mut merged = HyperLogLog::builder().bits(8).with_seeds(false).build();
+        merged.merge(&seeded).unwrap();
+        assert_eq!(merged.hashes(), seeded.hashes());
+        assert!(merged.seeds().is_empty());
+
+        // Derived sketches stay seedless
+        assert!(hll.fold(6).unwrap().seeds().is_empty());
+        assert!(hll
+            .split_registers(3)
+            .iter()
+            .all(|part| part.seeds().is_empty()));
+
+        hll.clear();
+        assert!(hll.is_empty());
+        assert!(hll.seeds().is_empty());
+    }
+
+    #[test]
+    fn test_add_report_returns_displaced_register() {
+        let mut hll = HyperLogLog::new(6);
+        let mut plain = HyperLogLog::new(6);
+        for seed in 0..2_000u64 {
+            let hash = splitmix(seed);
+            let register = (hash & 0x3F) as usize;
+            let before = (hll.hashes()[register], hll.seed(register));
+
+            let report = hll.add_report(seed, hash);
+            assert_eq!(report.is_some(), plain.add(seed, hash));
+            match report {
+                Some(previous) => {
+                    assert_eq!(previous, before);
+                    assert_eq!(hll.hashes()[register], hash);
+                    assert_eq!(hll.seed(register), seed);
+                }
+                None => assert_eq!((hll.hashes()[register], hll.seed(register)), before),
+            }
+        }
+        assert_eq!(hll, plain);
+    }
+
+    #[test]
+    fn test_small_range_correction_fixes_sparse_sketches() {
+        for n in [1u64, 10, 100] {
+            let mut hll = HyperLogLog::new(12);
+            for seed in 0..n {
+                hll.add(seed, splitmix(seed));
+            }
+
+            let corrected = hll.count_with_small_range_correction();
+            let raw = hll.count();
+            let n = n as f64;
+            assert!(
+                (corrected - n).abs() <= 0.05 * n + 0.5,
+                "{} items: corrected estimate {}",
+                n,
+                corrected
+            );
+            assert!(
+                (raw - n).abs() > 10.0 * (corrected - n).abs().max(1.0),
+                "{} items: raw {} vs corrected {}",
+                n,
+                raw,
+                corrected
+            );
+        }
+
+        assert_eq!(
+            HyperLogLog::new(12).count_with_small_range_correction(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_small_range_correction_defers_to_count_when_dense() {
+        let mut hll = HyperLogLog::new(8);
+        for seed in 0..100_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        assert_eq!(hll.count_with_small_range_correction(), hll.count());
+    }
+
+    #[test]
+    fn test_bias_table_shifts_estimate_predictably() {
+        let fill = |hll: &mut HyperLogLog| {
+            for seed in 0..5_000u64 {
+                hll.add(seed, splitmix(seed));
+            }
+        };
+        let mut plain = HyperLogLog::new(10);
+        fill(&mut plain);
+        let raw = plain.count();
+        assert_eq!(plain.bias_table(), &BiasTable::default());
+
+        // Bias rising linearly with raw / m removes a known fraction
+        let table = BiasTable::new(vec![(0.0, 0.0), (100.0, 10.0)]);
+        let mut corrected = HyperLogLog::with_bias_table(10, table.clone());
+        fill(&mut corrected);
+        assert_eq!(corrected, plain);
+        assert!((corrected.count() - 0.9 * raw).abs() < 1e-6 * raw);
+        assert_eq!(corrected.count_stable(), corrected.count());
+
+        // Estimates outside the table's range are left alone
+        let narrow = BiasTable::new(vec![(0.0, 1.0), (1.0, 1.0)]);
+        let mut untouched = HyperLogLog::with_bias_table(10, narrow);
+        fill(&mut untouched);
+        assert_eq!(untouched.count(), raw);
+
+        // Derived sketches keep the table
+        assert_eq!(corrected.fold(8).unwrap().bias_table(), &table);
+        assert_eq!(HyperLogLog::with_bias_table(10, table).count(), 0.0);
+    }
+
+    #[test]
+    fn test_count_estimate_interval_scales_with_bits() {
+        let relative_width = |bits: u8| {
+            let mut hll = HyperLogLog::new(bits);
+            for seed in 0..200_000u64 {
+                hll.add(seed, splitmix(seed));
+            }
+            let estimate = hll.count_estimate();
+            assert_eq!(estimate.value, hll.count());
+            assert!(estimate.lower <= estimate.value && estimate.value <= estimate.upper);
+            (estimate.upper - estimate.lower) / estimate.value
+        };
+
+        // Four times the registers halves the interval
+        for bits in [6u8, 8, 10] {
+            let ratio = relative_width(bits) / relative_width(bits + 2);
+            assert!((ratio - 2.0).abs() < 1e-9, "{} bits: ratio {}", bits, ratio);
+        }
+        assert!((relative_width(12) - 2.0 * Z_95 * 1.04 / 64.0).abs() < 1e-12);
+
+        let mut hll = HyperLogLog::new(12);
+        for seed in 0..200_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let estimate = hll.count_estimate();
+        assert!(
+            estimate.lower < 200_000.0 && 200_000.0 < estimate.upper,
+            "{:?}",
+            estimate
+        );
+
+        let empty = HyperLogLog::new(12).count_estimate();
+        assert_eq!(empty, Estimate::default());
+        assert_eq!(empty.to_string(), "0 (±0.0%)");
+    }
+
+    #[test]
+    fn test_add_all_matches_sequential_adds() {
+        let items: Vec<(u64, u64)> = (0..5_000u64).map(|seed| (seed, splitmix(seed))).collect();
+
+        for start in [HyperLogLog::new(8), HyperLogLog::new_lazy_seeds(8)] {
+            let mut sequential = start.clone();
+            let mut improved = HashSet::new();
+            for &(seed, hash) in &items {
+                if sequential.add(seed, hash) {
+                    improved.insert(hash & 0xFF);
+                }
+            }
+
+            let mut batched = start.clone();
+            assert_eq!(batched.add_all(items.iter().copied()), improved.len());
+            assert_eq!(batched, sequential);
+            assert_eq!(batched.count(), sequential.count());
+
+            // Replaying the same batch changes nothing
+            assert_eq!(batched.add_all(items.iter().copied()), 0);
+        }
+        assert_eq!(HyperLogLog::new(8).add_all([]), 0);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut hll = HyperLogLog::new(4);
+        hll.add(1, 0x1234);
+        hll.add(2, 0x5678_0002);
+
+        let json = hll.to_json();
+        let restored = HyperLogLog::from_json(4, &json);
+
+        assert_eq!(hll.hashes(), restored.hashes());
+        // Seeds are not preserved in JSON
+        assert_eq!(restored.seeds(), &[0u64; 16]);
+    }
+
+    #[test]
+    fn test_same_hashes_survives_json_roundtrip() {
+        let mut hll = HyperLogLog::new(6);
+        for seed in 1..300u64 {
+            hll.add(seed, splitmix(seed));
+        }
+
+        let restored = HyperLogLog::from_json(6, &hll.to_json());
+        assert_ne!(restored, hll);
+        assert!(restored.same_hashes(&hll));
+        assert!(hll.same_hashes(&restored));
+        assert_eq!(restored.count(), hll.count());
+
+        // Stray seeds on empty registers don't matter either
+        let mut noisy = HyperLogLog::new(6);
+        noisy.seeds.set(0, 99);
+        assert_ne!(noisy, HyperLogLog::new(6));
+        assert!(noisy.same_hashes(&HyperLogLog::new(6)));
+
+        // Different hashes or widths still differ
+        let mut changed = restored.clone();
+        changed.add_hash(0);
+        assert!(!changed.same_hashes(&hll));
+        assert!(!HyperLogLog::new(6).same_hashes(&HyperLogLog::new(7)));
+    }
+
+    #[test]
+    fn test_json_handles_invalid_input() {
+        // Invalid JSON returns default state
+        let hll = HyperLogLog::from_json(4, "not valid json");
+        assert_eq!(hll.hashes().len(), 16);
+        assert!(hll.hashes().iter().all(|&h| h == u64::MAX));
+    }
+
+    #[test]
+    fn test_json_versioned_roundtrip() {
+        let mut hll = HyperLogLog::new(6);
+        for seed in 0..200u64 {
+            hll.add_hash(splitmix(seed));
+        }
+
+        let json = hll.to_json_versioned();
+        assert!(json.starts_with(r#"{"v":1,"bits":6,"hashes":["#));
+
+        let restored = HyperLogLog::from_json_versioned(6, &json).unwrap();
+        assert_eq!(restored, hll);
+        assert_eq!(restored.count(), hll.count());
+    }
+
+    #[test]
+    fn test_json_versioned_reads_legacy_array() {
+        let json = r#"  ["100", "200", "18446744073709551615", "300"]"#;
+        let hll = HyperLogLog::from_json_versioned(2, json).unwrap();
+        assert_eq!(hll.hashes(), &[100, 200, u64::MAX, 300]);
+        assert_eq!(hll, HyperLogLog::from_json(2, json));
+    }
+
+    #[test]
+    fn test_json_versioned_rejects_bad_envelopes() {
+        assert_eq!(
+            HyperLogLog::from_json_versioned(4, r#"{"v":2,"bits":4,"hashes":[]}"#),
+            Err(ParseError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            HyperLogLog::from_json_versioned(4, r#"{"v":1,"bits":5,"hashes":[]}"#),
+            Err(ParseError::BitsMismatch {
+                expected: 4,
+                found: 5
+            })
+        );
+        assert_eq!(
+            HyperLogLog::from_json_versioned(4, r#"{"v":1,"bits":4}"#),
+            Err(ParseError::InvalidJson)
+        );
+        assert_eq!(
+            HyperLogLog::from_json_versioned(4, r#"{"v":1,"bits":4,"hashes":[1]}"#),
+            Err(ParseError::InvalidJson)
+        );
+        assert_eq!(
+            HyperLogLog::from_json_versioned(4, "not json"),
+            Err(ParseError::InvalidJson)
+        );
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let mut hll = HyperLogLog::new(8);
+        for seed in 0..100u64 {
+            hll.add(seed, splitmix(seed));
+        }
+
+        let bytes = hll.to_bytes();
+        assert_eq!(bytes.len(), BYTES_HEADER_LEN + 256 * 16);
+        let restored = HyperLogLog::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, hll);
+        assert_eq!(restored.count(), hll.count());
+
+        // Lazy seed storage writes the same layout as dense storage
+        let mut lazy = HyperLogLog::new_lazy_seeds(8);
+        for seed in 0..100u64 {
+            lazy.add(seed, splitmix(seed));
+        }
+        assert_eq!(lazy.to_bytes(), bytes);
+
+        // Hashes without the seeds flag restore with seeds cleared
+        let mut unseeded = vec![hll.bits(), 0];
+        unseeded.extend_from_slice(&bytes[BYTES_HEADER_LEN..BYTES_HEADER_LEN + 256 * 8]);
+        let restored = HyperLogLog::from_bytes(&unseeded).unwrap();
+        assert_eq!(restored.hashes(), hll.hashes());
+        assert!(restored.seeds().iter().all(|&seed| seed == 0));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_truncated_and_garbage_input() {
+        let bytes = HyperLogLog::new(4).to_bytes();
+
+        for length in 0..bytes.len() {
+            assert_eq!(
+                HyperLogLog::from_bytes(&bytes[..length]),
+                Err(ParseError::InvalidBytes),
+                "truncated to {} bytes",
+                length
+            );
+        }
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(
+            HyperLogLog::from_bytes(&extended),
+            Err(ParseError::InvalidBytes)
+        );
+
+        for (index, value) in [(0, 0), (0, MAX_HLL_BITS + 1), (0, 0xFF), (1, 2), (1, 0xFF)] {
+            let mut corrupted = bytes.clone();
+            corrupted[index] = value;
+            assert_eq!(
+                HyperLogLog::from_bytes(&corrupted),
+                Err(ParseError::InvalidBytes)
+            );
+        }
+
+        let mut state = 42u64;
+        for _ in 0..100 {
+            let length = (splitmix_next(&mut state) % 600) as usize;
+            let garbage: Vec<u8> = (0..length)
+                .map(|_| splitmix_next(&mut state) as u8)
+                .collect();
+            // Must not panic; random bytes almost never form a valid header
+            let _ = HyperLogLog::from_bytes(&garbage);
+        }
+    }
+
+    #[test]
+    fn test_apply_single_to_json_matches_full_cycle() {
+        let bits = 4;
+        let mut base = HyperLogLog::new(bits);
+        for seed in 0..8u64 {
+            base.add(seed, splitmix(seed));
+        }
+        let json = base.to_json();
+
+        for register in 0..16usize {
+            let current = base.hashes()[register];
+            let candidates = [
+                register as u64,
+                (current.saturating_sub(16) & !0xF) | register as u64,
+                (current & !0xF) | register as u64,
+                (current.saturating_add(16) & !0xF) | register as u64,
+                !0xF | register as u64,
+            ];
+            for hash in candidates {
+                let mut expected = HyperLogLog::from_json(bits, &json);
+                let improved = expected.add_hash(hash);
+
+                let result = HyperLogLog::apply_single_to_json(&json, bits, register, hash);
+                assert_eq!(
+                    result,
+                    Ok((improved, expected.to_json())),
+                    "register {}",
+                    register
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_single_to_json_falls_back_for_other_layouts() {
+        let bits = 2;
+        for json in [
+            r#"[ "5", "6", "7", "8" ]"#,
+            r#"["5","6"]"#,
+            r#"["05","6","7","8"]"#,
+            r#"["5","6","7","8","9"]"#,
+            r#"["5","x","7","8"]"#,
+            "not json",
+        ] {
+            let mut expected = HyperLogLog::from_json(bits, json);
+            let improved = expected.add_hash(0x1);
+
+            let result = HyperLogLog::apply_single_to_json(json, bits, 1, 0x1);
+            assert_eq!(result, Ok((improved, expected.to_json())), "{}", json);
+        }
+
+        assert_eq!(
+            HyperLogLog::apply_single_to_json(r#"["5","6","7","8"]"#, bits, 2, 0x1),
+            Err(ParseError::RegisterMismatch {
+                register: 2,
+                hash: 0x1
+            })
+        );
+    }
+
+    #[test]
+    fn test_fold_matches_native_sketch() {
+        let mut native = HyperLogLog::new(12);
+        let mut wide = HyperLogLog::new(16);
+        for seed in 0..20_000u64 {
+            native.add(seed, splitmix(seed));
+            wide.add(seed, splitmix(seed));
+        }
+
+        let folded = wide.fold(12).unwrap();
+        assert_eq!(folded.bits(), 12);
+        assert_eq!(folded, native);
+        assert_eq!(folded.seeds(), native.seeds());
+        assert_eq!(folded.count(), native.count());
+    }
+
+    #[test]
+    fn test_fold_shrinks_storage_and_keeps_estimate() {
+        // Mirrors the server compacting an idle function's sketch
+        for n in [1_000u64, 10_000, 100_000] {
+            let mut hll = HyperLogLog::new(DEFAULT_HLL_BITS);
+            for seed in 0..n {
+                hll.add(seed, splitmix(seed));
+            }
+
+            let compacted = hll.fold(3).unwrap();
+            assert_eq!(compacted.hashes().len(), 8);
+            assert!(compacted.memory_usage() < hll.memory_usage());
+            assert_eq!(compacted.verify_work(splitmix), Ok(()));
+
+            let error = (compacted.count() - n as f64).abs() / n as f64;
+            assert!(
+                error < 2.0 * HyperLogLog::relative_error_for_bits(3),
+                "n = {}: estimate {} off by {:.1}%",
+                n,
+                compacted.count(),
+                error * 100.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_fold_same_bits_is_identity_and_wider_errors() {
+        let mut hll = HyperLogLog::new_lazy_seeds(8);
+        for seed in 0..300u64 {
+            hll.add(seed, splitmix(seed));
+        }
+
+        let same = hll.fold(8).unwrap();
+        assert_eq!(same, hll);
+        assert_eq!(same.fold(8).unwrap(), same);
+        assert!(matches!(same.seeds, Seeds::Lazy(_)));
+
+        assert_eq!(
+            hll.fold(9),
+            Err(MergeError::BitsMismatch {
+                expected: 8,
+                found: 9
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_work_reports_first_bad_register() {
+        let mut hll = HyperLogLog::new(6);
+        for seed in 0..500u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        assert_eq!(hll.verify_work(splitmix), Ok(()));
+        assert_eq!(HyperLogLog::new(6).verify_work(splitmix), Ok(()));
+
+        // Forge two registers; only the lower index is reported
+        let mut hashes = hll.hashes().to_vec();
+        let seeds = hll.seeds().to_vec();
+        let (first, second) = (3, 40);
+        hashes[first] = (hashes[first] & !0x3F).wrapping_sub(64) | first as u64;
+        hashes[second] = (hashes[second] & !0x3F).wrapping_sub(64) | second as u64;
+        let forged = HyperLogLog::from_parts(6, hashes, Some(seeds.clone())).unwrap();
+        assert_eq!(
+            forged.verify_work(splitmix),
+            Err(VerifyError::HashMismatch {
+                register: first,
+                seed: seeds[first],
+                stored: forged.hashes()[first],
+                computed: splitmix(seeds[first]),
+            })
+        );
+
+        // A genuine hash moved into a register it doesn't select
+        let mut hashes = vec![u64::MAX; 64];
+        let mut seeds = vec![0; 64];
+        let hash = splitmix(7);
+        let wrong = ((hash as usize) + 1) & 0x3F;
+        hashes[wrong] = hash;
+        seeds[wrong] = 7;
+        let moved = HyperLogLog::from_parts(6, hashes, Some(seeds)).unwrap();
+        assert_eq!(
+            moved.verify_work(splitmix),
+            Err(VerifyError::WrongRegister {
+                register: wrong,
+                hash
+            })
+        );
+    }
+
+    #[test]
+    fn test_estimates_agree_across_construction_paths() {
+        let mut direct = HyperLogLog::new(10);
+        for seed in 0..5_000u64 {
+            direct.add(seed, splitmix(seed));
+        }
+        let from_json = HyperLogLog::from_json(10, &direct.to_json());
+        let from_bytes = HyperLogLog::from_bytes(&direct.to_bytes()).unwrap();
+
+        for (a, b) in [
+            (&direct, &from_json),
+            (&direct, &from_bytes),
+            (&from_json, &from_bytes),
+        ] {
+            assert!(HyperLogLog::estimates_agree(a, b, 0.0));
+        }
+
+        assert!(HyperLogLog::estimates_agree(
+            &HyperLogLog::new(10),
+            &HyperLogLog::new(10),
+            0.0
+        ));
+        let mut half = HyperLogLog::new(10);
+        for seed in 0..2_500u64 {
+            half.add(seed, splitmix(seed));
+        }
+        assert!(!HyperLogLog::estimates_agree(&direct, &half, 0.1));
+        assert!(HyperLogLog::estimates_agree(&direct, &half, 0.9));
+    }
+
+    #[test]
+    fn test_filled_registers_and_is_empty() {
+        let mut hll = HyperLogLog::new(6);
+        assert_eq!(hll.filled_registers(), 0);
+        assert!(hll.is_empty());
+
+        for seed in 0..20u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let filled = hll.hashes().iter().filter(|&&h| h != u64::MAX).count();
+        assert!(filled > 0 && filled < 64);
+        assert_eq!(hll.filled_registers(), filled);
+        assert!(!hll.is_empty());
+
+        // Raw register writes are picked up too
+        hll.hashes_mut()[0] = u64::MAX;
+        hll.hashes_mut()[1] = u64::MAX;
+        let filled = hll.hashes().iter().filter(|&&h| h != u64::MAX).count();
+        assert_eq!(hll.filled_registers(), filled);
+
+        let mut saturated = HyperLogLog::new(6);
+        for register in 0..64u64 {
+            saturated.add_hash(register);
+        }
+        assert_eq!(saturated.filled_registers(), 64);
+        assert!(!saturated.is_empty());
+    }
+
+    #[test]
+    fn test_split_registers_round_trips_through_merge_many() {
+        let mut hll = HyperLogLog::new(8);
+        for seed in 0..1_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+
+        for n in [1, 3, 7, 256, 300] {
+            let pieces = hll.split_registers(n);
+            assert_eq!(pieces.len(), n);
+
+            // Every filled register lives in exactly one piece
+            for register in 0..256 {
+                let holders = pieces
+                    .iter()
+                    .filter(|piece| piece.hashes()[register] != u64::MAX)
+                    .count();
+                let expected = usize::from(hll.hashes()[register] != u64::MAX);
+                assert_eq!(holders, expected, "n = {}, register {}", n, register);
+            }
+
+            let merged = HyperLogLog::merge_many(&pieces).unwrap();
+            assert_eq!(merged, hll);
+            assert_eq!(merged.seeds(), hll.seeds());
+        }
+
+        assert_eq!(hll.split_registers(0).len(), 1);
+    }
+
+    #[test]
+    fn test_relative_error_by_bits() {
+        let hll = HyperLogLog::new(12);
+        assert!((hll.relative_error() - 0.016).abs() < 0.0005);
+        assert_eq!(
+            hll.relative_error(),
+            HyperLogLog::relative_error_for_bits(12)
+        );
+
+        // Clamped like `new`, and shrinking as registers are added
+        assert_eq!(
+            HyperLogLog::relative_error_for_bits(0),
+            HyperLogLog::relative_error_for_bits(1)
+        );
+        assert_eq!(
+            HyperLogLog::relative_error_for_bits(MAX_HLL_BITS + 5),
+            HyperLogLog::relative_error_for_bits(MAX_HLL_BITS)
+        );
+        for bits in 1..MAX_HLL_BITS {
+            assert!(
+                HyperLogLog::relative_error_for_bits(bits + 1)
+                    < HyperLogLog::relative_error_for_bits(bits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_clear_resets_registers_in_place() {
+        for mut hll in [HyperLogLog::new(10), HyperLogLog::new_lazy_seeds(10)] {
+            for seed in 1..2_000u64 {
+                hll.add(seed, splitmix(seed));
+            }
+            let allocation = hll.hashes().as_ptr();
+
+            hll.clear();
+            assert_eq!(hll.count(), 0.0);
+            assert_eq!(hll.bits(), 10);
+            assert_eq!(hll.hashes().as_ptr(), allocation);
+            assert!(hll.hashes().iter().all(|&hash| hash == u64::MAX));
+            assert!(hll.seeds().iter().all(|&seed| seed == 0));
+            assert_eq!((hll.rho_sum, hll.filled), hll.scan_accounting());
+
+            // Still usable afterwards
+            assert!(hll.add(7, splitmix(7)));
+            assert_eq!(hll.filled_registers(), 1);
+        }
+    }
+
+    #[test]
+    fn test_memory_usage_tracks_seed_storage() {
+        let dense = HyperLogLog::new(12);
+        let kb = 1024;
+        let usage = dense.memory_usage();
+        assert!(
+            (64 * kb..65 * kb).contains(&usage),
+            "12-bit dense sketch reported {} bytes",
+            usage
+        );
+
+        let seedless = HyperLogLog::builder().bits(12).with_seeds(false).build();
+        assert!((32 * kb..33 * kb).contains(&seedless.memory_usage()));
+
+        // Lazy seeds start free and grow with recorded seeds
+        let mut lazy = HyperLogLog::new_lazy_seeds(12);
+        assert_eq!(lazy.memory_usage(), seedless.memory_usage());
+        for seed in 1..=100u64 {
+            lazy.add(seed, splitmix(seed));
+        }
+        let grown = lazy.memory_usage() - seedless.memory_usage();
+        assert!(
+            grown >= 100 * 16 && grown < 8 * kb,
+            "lazy seeds use {}",
+            grown
+        );
+
+        let table = BiasTable::new(vec![(0.0, 0.0); 4]);
+        assert_eq!(
+            HyperLogLog::with_bias_table(12, table).memory_usage(),
+            usage + 4 * 16
+        );
+    }
+
+    #[test]
+    fn test_merge_cost_estimate_is_register_count() {
+        for bits in 1..=MAX_HLL_BITS {
+            assert_eq!(HyperLogLog::new(bits).merge_cost_estimate(), 1 << bits);
+        }
+    }
+
+    #[test]
+    fn test_jaccard_similarity_tracks_set_overlap() {
+        let sketch = |seeds: std::ops::Range<u64>| {
+            let mut hll = HyperLogLog::new(12);
+            for seed in seeds {
+                hll.add(seed, splitmix(seed));
+            }
+            hll
+        };
+
+        let a = sketch(0..10_000);
+        assert_eq!(a.jaccard_similarity(&a.clone()), Ok(1.0));
+
+        let disjoint = sketch(10_000..20_000);
+        assert_eq!(a.jaccard_similarity(&disjoint), Ok(0.0));
+
+        // Half of each set is shared: |A ∩ B| / |A ∪ B| = 5k / 15k
+        let half = sketch(5_000..15_000);
+        let similarity = a.jaccard_similarity(&half).unwrap();
+        assert!(
+            (similarity - 1.0 / 3.0).abs() < 0.05,
+            "Expected about 1/3, got {}",
+            similarity
+        );
+        assert_eq!(half.jaccard_similarity(&a), Ok(similarity));
+        assert_eq!(a.jaccard_with_json(12, &half.to_json()), Ok(similarity));
+
+        assert_eq!(
+            HyperLogLog::new(12).jaccard_similarity(&HyperLogLog::new(12)),
+            Ok(1.0)
+        );
+        assert_eq!(
+            a.jaccard_similarity(&HyperLogLog::new(10)),
+            Err(MergeError::BitsMismatch {
+                expected: 12,
+                found: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_clears_stray_empty_register_seeds() {
+        let mut clean = HyperLogLog::new(4);
+        for seed in 1..6u64 {
+            clean.add(seed, splitmix(seed));
+        }
+
+        // Same hashes, but every empty register carries a garbage seed
+        let stray: Vec<u64> = clean
+            .iter()
+            .map(|(index, hash, seed)| {
+                if hash == u64::MAX {
+                    1000 + index as u64
+                } else {
+                    seed
+                }
+            })
+            .collect();
+        for lazy in [false, true] {
+            let mut dirty =
+                HyperLogLog::from_parts(4, clean.hashes().to_vec(), Some(stray.clone())).unwrap();
+            if lazy {
+                let mut lazy_dirty = HyperLogLog::new_lazy_seeds(4);
+                for (index, hash, seed) in dirty.iter() {
+                    lazy_dirty.hashes_mut()[index] = hash;
+                    lazy_dirty.seeds.set(index, seed);
+                }
+                dirty = lazy_dirty;
+            }
+            assert_ne!(dirty, clean);
+
+            dirty.canonicalize();
+            assert_eq!(dirty, clean);
+            for (_, hash, seed) in dirty.iter() {
+                if hash == u64::MAX {
+                    assert_eq!(seed, 0);
+                }
+            }
+            if let Seeds::Lazy(seeds) = &dirty.seeds {
+                assert_eq!(seeds.recorded.len(), clean.filled_registers());
+            }
+
+            // Idempotent
+            let once = dirty.clone();
+            dirty.canonicalize();
+            assert_eq!(dirty, once);
+        }
+    }
+
+    #[test]
+    fn test_intersection_estimate_over_overlapping_ranges() {
+        let sketch = |seeds: std::ops::Range<u64>| {
+            let mut hll = HyperLogLog::new(12);
+            for seed in seeds {
+                hll.add(seed, splitmix(seed));
+            }
+            hll
+        };
+        let a = sketch(0..20_000);
+
+        // 10k shared out of a 30k union; the error scales with the union
+        let b = sketch(10_000..30_000);
+        let shared = a.intersection_estimate(&b).unwrap();
+        assert!(
+            (shared - 10_000.0).abs() < 2_000.0,
+            "Expected about 10000, got {}",
+            shared
+        );
+        assert_eq!(b.intersection_estimate(&a), Ok(shared));
+
+        // A subset shares all of itself
+        let subset = sketch(0..10_000);
+        let shared = a.intersection_estimate(&subset).unwrap();
+        assert!((shared - subset.count()).abs() < 2_000.0);
+
+        // Identical sketches share their whole estimate
+        assert_eq!(a.intersection_estimate(&a), Ok(a.count()));
+
+        // Disjoint sets come out near zero, never negative
+        let disjoint = sketch(20_000..40_000);
+        let shared = a.intersection_estimate(&disjoint).unwrap();
+        assert!((0.0..2_000.0).contains(&shared), "got {}", shared);
+
+        assert!(a.intersection_estimate(&HyperLogLog::new(10)).is_err());
+    }
+
+    #[test]
+    fn test_merge_json_into_bytes_matches_struct_path() {
+        let mut stored = HyperLogLog::new(6);
+        let mut upload = HyperLogLog::new(6);
+        for seed in 0..300u64 {
+            stored.add(seed, splitmix(seed));
+            upload.add_hash(splitmix(seed + 150));
+        }
+
+        let merged =
+            HyperLogLog::merge_json_into_bytes(&stored.to_bytes(), 6, &upload.to_json()).unwrap();
+
+        let mut expected = stored.clone();
+        expected
+            .merge_with_stats(&HyperLogLog::from_json(6, &upload.to_json()))
+            .unwrap();
+        assert_eq!(merged, expected.to_bytes());
+        assert_eq!(HyperLogLog::from_bytes(&merged).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_merge_json_into_bytes_validates_inputs() {
+        let stored = HyperLogLog::new(6).to_bytes();
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored, 8, "[]"),
+            Err(ParseError::BitsMismatch {
+                expected: 8,
+                found: 6
+            })
+        );
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored, 6, "{}"),
+            Err(ParseError::InvalidJson)
+        );
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored[..10], 6, "[]"),
+            Err(ParseError::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn test_merge_json_into_bytes_rejects_wrong_length_and_invalid_registers() {
+        let stored = HyperLogLog::new(2).to_bytes();
+        let short = r#"["1", "2", "3"]"#;
+        let long = r#"["1", "2", "3", "4", "5"]"#;
+        let invalid = r#"["1", "2", "x", "4"]"#;
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored, 2, short),
+            Err(ParseError::LengthMismatch {
+                expected: 4,
+                found: 3
+            })
+        );
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored, 2, long),
+            Err(ParseError::LengthMismatch {
+                expected: 4,
+                found: 5
+            })
+        );
+        assert_eq!(
+            HyperLogLog::merge_json_into_bytes(&stored, 2, invalid),
+            Err(ParseError::InvalidRegister { register: 2 })
+        );
+
+        // Same verdicts as decoding the upload on its own
+        for json in [short, long, invalid] {
+            assert_eq!(
+                HyperLogLog::merge_json_into_bytes(&stored, 2, json).unwrap_err(),
+                HyperLogLog::from_json_checked(2, json).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_parts_valid() {
+        let mut original = HyperLogLog::new(4);
+        for seed in 0..40u64 {
+            original.add(seed, splitmix(seed));
+        }
+
+        let rebuilt = HyperLogLog::from_parts(
+            4,
+            original.hashes().to_vec(),
+            Some(original.seeds().to_vec()),
+        )
+        .unwrap();
+        assert_eq!(rebuilt, original);
+        assert_eq!(rebuilt.count(), original.count());
+
+        let unseeded = HyperLogLog::from_parts(4, original.hashes().to_vec(), None).unwrap();
+        assert_eq!(unseeded.hashes(), original.hashes());
+        assert!(unseeded.seeds().iter().all(|&seed| seed == 0));
+    }
+
+    #[test]
+    fn test_from_parts_rejects_length_mismatch() {
+        assert_eq!(
+            HyperLogLog::from_parts(4, vec![u64::MAX; 15], None),
+            Err(ParseError::LengthMismatch {
+                expected: 16,
+                found: 15
+            })
+        );
+        assert_eq!(
+            HyperLogLog::from_parts(4, vec![u64::MAX; 16], Some(vec![0; 32])),
+            Err(ParseError::LengthMismatch {
+                expected: 16,
+                found: 32
+            })
+        );
+        // Out-of-range bits are clamped before the length check
+        assert!(HyperLogLog::from_parts(0, vec![u64::MAX; 2], None).is_ok());
+    }
+
+    #[test]
+    fn test_write_json_matches_to_json() {
+        for bits in [1u8, 4, 10] {
+            let mut hll = HyperLogLog::new(bits);
+            for seed in 0..500u64 {
+                hll.add(seed, splitmix(seed));
+            }
+
+            let mut out = Vec::new();
+            hll.write_json(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), hll.to_json());
+        }
+    }
+
+    #[test]
+    fn test_from_json_checked_rejects_mismatched_arrays() {
+        let mut hll = HyperLogLog::new(2);
+        hll.add_hash(0x1234);
+        let json = hll.to_json();
+        assert_eq!(HyperLogLog::from_json_checked(2, &json), Ok(hll.clone()));
+        assert_eq!(HyperLogLog::from_json(2, &json), hll);
+
+        // Over-long: read at fewer bits than were written
+        assert_eq!(
+            HyperLogLog::from_json_checked(1, &json),
+            Err(ParseError::LengthMismatch {
+                expected: 2,
+                found: 4
+            })
+        );
+        // Short: read at more bits than were written
+        assert_eq!(
+            HyperLogLog::from_json_checked(3, &json),
+            Err(ParseError::LengthMismatch {
+                expected: 8,
+                found: 4
+            })
+        );
+        // Non-numeric, negative and out-of-range values
+        for bad in ["abc", "-1", "18446744073709551616"] {
+            let json = format!(r#"["100", "200", "{}", "300"]"#, bad);
+            assert_eq!(
+                HyperLogLog::from_json_checked(2, &json),
+                Err(ParseError::InvalidRegister { register: 2 })
+            );
+            // The lenient path still skips the bad value
+            assert_eq!(
+                HyperLogLog::from_json(2, &json).hashes(),
+                &[100, 200, u64::MAX, 300]
+            );
+        }
+        assert_eq!(
+            HyperLogLog::from_json_checked(2, "[1, 2, 3, 4]"),
+            Err(ParseError::InvalidJson)
+        );
+    }
+
+    #[test]
+    fn test_json_handles_partial_data() {
+        // Fewer values than registers
+        let json = r#"["100", "200"]"#;
+        let hll = HyperLogLog::from_json(4, json); // 16 registers
+
+        assert_eq!(hll.hashes()[0], 100);
+        assert_eq!(hll.hashes()[1], 200);
+        assert_eq!(hll.hashes()[2], u64::MAX);
+    }
+
+    #[test]
+    fn test_jaccard_with_json_matches_struct_path() {
+        let mut a = HyperLogLog::new(8);
+        let mut b = HyperLogLog::new(8);
+        for seed in 0..2_000u64 {
+            a.add(seed, splitmix(seed));
+        }
+        for seed in 1_000..3_000u64 {
+            b.add(seed, splitmix(seed));
+        }
+
+        let json = b.to_json();
+        let from_json = a.jaccard_with_json(8, &json).unwrap();
+        let materialized = HyperLogLog::from_json(8, &json);
+        let from_struct = a.jaccard_registers(materialized.hashes().iter().copied());
+
+        assert_eq!(from_json, from_struct);
+        assert!(from_json > 0.0 && from_json < 1.0);
+        assert_eq!(a.jaccard_with_json(8, &a.to_json()).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_jaccard_with_json_rejects_bad_input() {
+        let hll = HyperLogLog::new(4);
+        assert_eq!(
+            hll.jaccard_with_json(5, "[]"),
+            Err(ParseError::BitsMismatch {
+                expected: 4,
+                found: 5
+            })
+        );
+        assert_eq!(
+            hll.jaccard_with_json(4, "not valid json"),
+            Err(ParseError::InvalidJson)
+        );
+        // Two empty sketches are identical
+        assert_eq!(hll.jaccard_with_json(4, "[]"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_reset_register_clears_only_target() {
+        let mut hll = HyperLogLog::new(6);
+        for seed in 0..500u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let before = hll.clone();
+        let filled = |hll: &HyperLogLog| hll.iter().filter(|&(_, h, _)| h != u64::MAX).count();
+        let target = hll.iter().find(|&(_, h, _)| h != u64::MAX).unwrap().0;
+
+        assert!(hll.reset_register(target));
+        assert_eq!(filled(&hll), filled(&before) - 1);
+        assert_eq!(hll.hashes()[target], u64::MAX);
+        assert_eq!(hll.seed(target), 0);
+        for (index, hash, seed) in before.iter().filter(|&(i, _, _)| i != target) {
+            assert_eq!(hll.hashes()[index], hash);
+            assert_eq!(hll.seed(index), seed);
+        }
+
+        // Running accounting follows the reset
+        assert_eq!(hll.count(), scan_estimate(&hll));
+        assert!(!hll.reset_register(64));
+    }
+
+    #[test]
+    fn test_merge_disjoint_ranges_estimates_combined_cardinality() {
+        let mut first = HyperLogLog::new(12);
+        let mut second = HyperLogLog::new(12);
+        let mut combined = HyperLogLog::new(12);
+
+        for seed in 0..10_000u64 {
+            first.add(seed, splitmix(seed));
+            combined.add(seed, splitmix(seed));
+        }
+        for seed in 10_000..20_000u64 {
+            second.add(seed, splitmix(seed));
+            combined.add(seed, splitmix(seed));
+        }
+
+        let merged = HyperLogLog::union(&first, &second).unwrap();
+        assert_eq!(merged, combined);
+
+        let mut in_place = first.clone();
+        in_place.merge(&second).unwrap();
+        assert_eq!(in_place, merged);
+
+        // Same tolerance as test_count_estimates_cardinality
+        let estimate = merged.count();
+        assert!(
+            estimate > 20_000.0 * 0.95 && estimate < 20_000.0 * 1.05,
+            "Expected estimate near 20000, got {}",
+            estimate
+        );
+        assert!(estimate > first.count());
+    }
+
+    #[test]
+    fn test_merge_rejects_bits_mismatch_without_changes() {
+        let mut a = HyperLogLog::new(8);
+        a.add(1, splitmix(1));
+        let before = a.clone();
+
+        assert_eq!(
+            a.merge(&HyperLogLog::new(10)),
+            Err(MergeError::BitsMismatch {
+                expected: 8,
+                found: 10
+            })
+        );
+        assert_eq!(a, before);
+        assert!(HyperLogLog::union(&a, &HyperLogLog::new(10)).is_err());
+    }
+
+    #[test]
+    fn test_merge_dedup_seeds_flags_seed_zero_everywhere() {
+        let mut honest = HyperLogLog::new(8);
+        for seed in 1..400u64 {
+            honest.add(seed, splitmix(seed));
+        }
+
+        // A buggy client that never recorded seeds
+        let mut degenerate = HyperLogLog::new(8);
+        for seed in 1_000..1_400u64 {
+            degenerate.add_hash(splitmix(seed));
+        }
+        // Plus one register proven with a real seed
+        let lone = (1u64..)
+            .map(|seed| (seed, splitmix(seed) >> 8 << 8))
+            .find(|&(_, hash)| hash < honest.hashes()[0])
+            .unwrap();
+        degenerate.reset_register(0);
+        assert!(degenerate.add(lone.0, lone.1));
+
+        let mut merged = honest.clone();
+        let refused = merged.merge_dedup_seeds(&degenerate).unwrap();
+        assert_eq!(refused, vec![0]);
+
+        // Only the seeded register was taken
+        for (index, hash, seed) in merged.iter() {
+            if index == 0 {
+                assert_eq!((seed, hash), lone);
+            } else {
+                assert_eq!((hash, seed), (honest.hashes()[index], honest.seed(index)));
+            }
+        }
+
+        // A clean sketch merges like merge()
+        let mut plain = honest.clone();
+        let mut deduped = honest.clone();
+        let mut other = HyperLogLog::new(8);
+        for seed in 5_000..5_400u64 {
+            other.add(seed, splitmix(seed));
+        }
+        plain.merge(&other).unwrap();
+        assert!(deduped.merge_dedup_seeds(&other).unwrap().is_empty());
+        assert_eq!(deduped, plain);
+    }
+
+    #[test]
+    fn test_merge_dedup_seeds_ties_match_merge() {
+        let mut a = HyperLogLog::new(4);
+        let mut b = HyperLogLog::new(4);
+        a.add(900, 0x0100_0000_0000_0003);
+        b.add(12, 0x0100_0000_0000_0003);
+        a.add(5, 0x0200_0000_0000_0007);
+        b.add(60, 0x0200_0000_0000_0007);
+        a.add_hash(0x0300_0000_0000_0009);
+        b.add(77, 0x0300_0000_0000_0009);
+
+        for (left, right) in [(&a, &b), (&b, &a)] {
+            let mut plain = left.clone();
+            let mut deduped = left.clone();
+            plain.merge(right).unwrap();
+            assert!(deduped.merge_dedup_seeds(right).unwrap().is_empty());
+            assert_eq!(deduped.to_bytes(), plain.to_bytes());
+            assert_eq!(
+                (deduped.seed(3), deduped.seed(7), deduped.seed(9)),
+                (12, 5, 77)
+            );
+        }
+
+        // A refused seed can't win a tie either
+        let mut dup = HyperLogLog::new(4);
+        dup.add(1, 0x0100_0000_0000_0003);
+        dup.add(1, 0x0100_0000_0000_0004);
+        let mut target = a.clone();
+        assert_eq!(target.merge_dedup_seeds(&dup).unwrap(), vec![1]);
+        assert_eq!(target.seed(3), 900);
+    }
+
+    #[test]
+    fn test_merge_capped_respects_cap() {
+        let mut source = HyperLogLog::new(4);
+        for register in 0..10u64 {
+            source.add(100 + register, register);
+        }
+
+        let mut target = HyperLogLog::new(4);
+        let stats = target.merge_capped(&source, 3).unwrap();
+        assert_eq!(
+            stats,
+            MergeStats {
+                improved: 3,
+                truncated: true,
+                rejected: 0
+            }
+        );
+        assert_eq!(
+            target.hashes().iter().filter(|&&h| h != u64::MAX).count(),
+            3
+        );
+        assert_eq!(target.seeds()[2], 102);
+        assert_eq!(target.hashes()[3], u64::MAX);
+
+        // A second call picks up the remaining improvements
+        let stats = target.merge_capped(&source, 100).unwrap();
+        assert_eq!(
+            stats,
+            MergeStats {
+                improved: 7,
+                truncated: false,
+                rejected: 0
+            }
+        );
+        assert_eq!(target.hashes(), source.hashes());
+        assert_eq!(target.seeds(), source.seeds());
+    }
+
+    #[test]
+    fn test_merge_verified_rejects_tampered_registers() {
+        let mut source = HyperLogLog::new(6);
+        for seed in 0..300u64 {
+            source.add(seed, splitmix(seed));
+        }
+        let honest = source.clone();
+
+        // Tamper with one register's seed and move another's hash
+        let filled: Vec<usize> = (0..64)
+            .filter(|&r| source.hashes()[r] != u64::MAX)
+            .collect();
+        let (tampered, moved) = (filled[0], filled[1]);
+        source.seeds.set(tampered, source.seed(tampered) + 1);
+        let wrong_register = (source.hashes()[moved] & !0x3F) | (moved as u64 ^ 1);
+        source.hashes_mut()[moved] = wrong_register;
+
+        let mut target = HyperLogLog::new(6);
+        let stats = target.merge_verified(&source, splitmix).unwrap();
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.improved, filled.len() - 2);
+        assert!(!stats.truncated);
+        assert_eq!(target.verify_work(splitmix), Ok(()));
+        assert_eq!(target.hashes()[tampered], u64::MAX);
+        assert_eq!(target.hashes()[moved], u64::MAX);
+        for &register in &filled[2..] {
+            assert_eq!(target.hashes()[register], honest.hashes()[register]);
+            assert_eq!(target.seed(register), honest.seed(register));
+        }
+
+        // Registers that wouldn't change the target are not checked
+        let stats = target.merge_verified(&honest, |_| 0).unwrap();
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.improved, 0);
+
+        assert!(target
+            .merge_verified(&HyperLogLog::new(5), splitmix)
+            .is_err());
+    }
+
+    #[test]
+    fn test_improvements_to_submit_over_known_pairs() {
+        let mut local = HyperLogLog::new(3);
+        let mut server = HyperLogLog::new(3);
+        local.add(10, 0x100); // register 0: server empty
+        local.add(11, 0x201); // register 1: beats server
+        server.add(21, 0x301);
+        local.add(12, 0x402); // register 2: loses to server
+        server.add(22, 0x102);
+        local.add(13, 0x503); // register 3: tie, nothing to submit
+        server.add(23, 0x503);
+        server.add(24, 0x104); // register 4: local empty
+
+        let improvements = local.improvements_to_submit(&server).unwrap();
+        assert_eq!(improvements, vec![(0, 0x100, 10), (1, 0x201, 11)]);
+
+        // Submitting them brings the server up to the merged state
+        let mut expected = server.clone();
+        expected.merge(&local).unwrap();
+        for &(_, hash, seed) in &improvements {
+            assert!(server.add(seed, hash));
+        }
+        assert_eq!(server.hashes(), expected.hashes());
+        assert_eq!(local.improvements_to_submit(&server), Ok(vec![]));
+
+        assert!(local.improvements_to_submit(&HyperLogLog::new(4)).is_err());
+    }
+
+    #[test]
+    fn test_merge_tie_keeps_smaller_seed_in_either_order() {
+        let mut a = HyperLogLog::new(4);
+        let mut b = HyperLogLog::new(4);
+        // Same hashes found under different seeds
+        a.add(900, 0x0100_0000_0000_0003);
+        b.add(12, 0x0100_0000_0000_0003);
+        a.add(5, 0x0200_0000_0000_0007);
+        b.add(60, 0x0200_0000_0000_0007);
+        // A recorded seed beats an unrecorded one
+        a.add_hash(0x0300_0000_0000_0009);
+        b.add(77, 0x0300_0000_0000_0009);
+
+        let mut ab = a.clone();
+        let stats = ab.merge_with_stats(&b).unwrap();
+        assert_eq!(stats.improved, 0);
+
+        let mut ba = b.clone();
+        ba.merge_with_stats(&a).unwrap();
+
+        assert_eq!(ab, ba);
+        assert_eq!(ab.to_bytes(), ba.to_bytes());
+        assert_eq!(ab.seed(3), 12);
+        assert_eq!(ab.seed(7), 5);
+        assert_eq!(ab.seed(9), 77);
     }
 
     #[test]
-    fn test_new_creates_correct_size() {
-        let hll = HyperLogLog::new(4);
-        assert_eq!(hll.bits(), 4);
-        assert_eq!(hll.hashes().len(), 16);
-        assert_eq!(hll.seeds().len(), 16);
+    fn test_merge_capped_exact_cap_is_not_truncated() {
+        let mut source = HyperLogLog::new(4);
+        source.add(1, 0x01);
+        source.add(2, 0x02);
+
+        let mut target = HyperLogLog::new(4);
+        let stats = target.merge_capped(&source, 2).unwrap();
+        assert_eq!(stats.improved, 2);
+        assert!(!stats.truncated);
     }
 
     #[test]
-    fn test_new_normalizes_bits() {
-        // Too low - clamped to 1
-        let hll = HyperLogLog::new(0);
-        assert_eq!(hll.bits(), 1);
-        assert_eq!(hll.hashes().len(), 2);
+    fn test_merge_capped_rejects_bits_mismatch() {
+        let mut target = HyperLogLog::new(4);
+        let source = HyperLogLog::new(5);
+        assert_eq!(
+            target.merge_capped(&source, 10),
+            Err(MergeError::BitsMismatch {
+                expected: 4,
+                found: 5
+            })
+        );
+    }
 
-        // Too high - clamped to MAX_HLL_BITS
-        let hll = HyperLogLog::new(30);
-        assert_eq!(hll.bits(), MAX_HLL_BITS);
-        assert_eq!(hll.hashes().len(), 1 << MAX_HLL_BITS);
+    #[test]
+    fn test_count_stable_matches_exact_sum_at_max_bits() {
+        let bits = MAX_HLL_BITS;
+        let m = 1usize << bits;
+        // Empty registers first, then registers at the deepest rho: by the
+        // time the tiny terms arrive, a naive total is too large to hold them
+        let mut hashes = vec![u64::MAX; m];
+        for (register, hash) in hashes.iter_mut().enumerate().skip(m / 2) {
+            *hash = register as u64;
+        }
+        let hll = HyperLogLog::from_parts(bits, hashes, None).unwrap();
+        let rho = hll.max_rho() as i32;
+
+        let terms = || {
+            hll.hashes().iter().map(|&hash| {
+                if hash == u64::MAX {
+                    1.0
+                } else {
+                    2_f64.powi(-rho)
+                }
+            })
+        };
+        let naive: f64 = terms().sum();
+        let compensated = kahan_sum(terms());
+        let exact = (m / 2) as f64 + (m / 2) as f64 * 2_f64.powi(-rho);
+
+        assert_eq!(naive, (m / 2) as f64);
+        assert_ne!(naive, exact);
+        assert_eq!(compensated, exact);
+        assert_eq!(hll.count_stable(), harmonic_estimate(bits, exact));
+        assert_eq!(hll.count(), hll.count_stable());
+    }
+
+    /// Plain floating-point estimate straight from the registers
+    fn scan_estimate(hll: &HyperLogLog) -> f64 {
+        let sum: f64 = hll
+            .hashes()
+            .iter()
+            .filter(|&&hash| hash != u64::MAX)
+            .map(|&hash| 2_f64.powi(-(hll.register_rho(hash) as i32)))
+            .sum();
+        let empty = hll
+            .hashes()
+            .iter()
+            .filter(|&&hash| hash == u64::MAX)
+            .count();
+        if empty == hll.hashes().len() {
+            return 0.0;
+        }
+        harmonic_estimate(hll.bits(), sum + empty as f64)
     }
 
     #[test]
-    fn test_add_returns_true_on_improvement() {
-        let mut hll = HyperLogLog::new(4);
+    fn test_incremental_count_matches_full_scan() {
+        let mut hll = HyperLogLog::new(10);
 
-        // First add always improves from u64::MAX
-        assert!(hll.add(1, 0xFFFF_0000));
+        for seed in 0..50_000u64 {
+            hll.add(seed, splitmix(seed));
 
-        // Lower hash improves
-        assert!(hll.add(2, 0x0000_0000)); // Same register (bits 0-3)
+            if seed % 5_000 == 0 {
+                assert!(hll.accounting_fresh);
+                let (rho_sum, filled) = hll.scan_accounting();
+                assert_eq!(hll.rho_sum, rho_sum);
+                assert_eq!(hll.filled, filled);
 
-        // Higher hash doesn't improve
-        assert!(!hll.add(3, 0xFFFF_0000));
+                let expected = scan_estimate(&hll);
+                let relative = (hll.count() - expected).abs() / expected;
+                assert!(relative < 1e-12, "checkpoint {}: {}", seed, relative);
+            }
+        }
     }
 
     #[test]
-    fn test_add_tracks_seed() {
-        let mut hll = HyperLogLog::new(4);
+    fn test_incremental_count_tracks_merge_and_raw_access() {
+        let mut a = HyperLogLog::new(6);
+        let mut b = HyperLogLog::new(6);
+        for seed in 0..500u64 {
+            a.add(seed, splitmix(seed));
+            b.add(seed, splitmix(seed + 10_000));
+        }
 
-        // With 4 bits, register selection uses lower 4 bits
-        // Hash 0x0013 -> register 3 (0x13 & 0xF = 3)
-        hll.add(42, 0x0013);
-        assert_eq!(hll.seeds()[3], 42);
+        a.merge_capped(&b, usize::MAX).unwrap();
+        assert_eq!((a.rho_sum, a.filled), a.scan_accounting());
 
-        // Lower hash that still maps to register 3
-        // Hash 0x0003 -> register 3 (0x03 & 0xF = 3), and 0x0003 < 0x0013
-        hll.add(99, 0x0003);
-        assert_eq!(hll.seeds()[3], 99);
+        // Raw access invalidates the cache; count still sees the new value
+        a.hashes_mut()[0] = 0;
+        assert!(!a.accounting_fresh);
+        let expected = scan_estimate(&a);
+        assert!((a.count() - expected).abs() / expected < 1e-12);
+
+        // The next mutation resyncs
+        assert!(a.add(1, 1));
+        assert!(a.accounting_fresh);
+        assert_eq!((a.rho_sum, a.filled), a.scan_accounting());
     }
 
     #[test]
-    fn test_add_hash_sets_seed_to_zero() {
+    fn test_from_json_rebuilds_accounting() {
         let mut hll = HyperLogLog::new(4);
+        hll.add(1, 0x1234);
+        hll.add(2, 0x5678_0002);
 
-        hll.add_hash(0x0005); // Register 5
-        assert_eq!(hll.seeds()[5], 0);
+        let restored = HyperLogLog::from_json(4, &hll.to_json());
+        assert!(restored.accounting_fresh);
+        assert_eq!(restored.filled, 2);
+        assert_eq!(restored.count(), hll.count());
     }
 
     #[test]
-    fn test_count_empty_returns_zero() {
-        let hll = HyperLogLog::new(12);
-        assert_eq!(hll.count(), 0.0);
+    fn test_plausible_count_range_narrows_with_occupancy() {
+        let mut previous_width = f64::INFINITY;
+
+        for n in [50u64, 500, 5_000, 50_000] {
+            let mut hll = HyperLogLog::new(10);
+            for seed in 0..n {
+                hll.add(seed, splitmix(seed));
+            }
+
+            let (low, high) = hll.plausible_count_range();
+            let truth = n as f64;
+            assert!(
+                low <= truth && truth <= high,
+                "{} outside [{}, {}]",
+                n,
+                low,
+                high
+            );
+            assert!(low >= hll.iter().filter(|&(_, hash, _)| hash != u64::MAX).count() as f64);
+
+            let width = (high - low) / truth;
+            assert!(
+                width < previous_width,
+                "relative width {} did not shrink at n={}",
+                width,
+                n
+            );
+            previous_width = width;
+        }
     }
 
     #[test]
-    fn test_count_estimates_cardinality() {
-        let mut hll = HyperLogLog::new(12);
-        let n = 10_000u64;
+    fn test_plausible_count_range_empty_and_sparse() {
+        assert_eq!(HyperLogLog::new(10).plausible_count_range(), (0.0, 0.0));
 
-        for seed in 0..n {
+        // A single filled register pins the low end to one item and leaves
+        // the high end several times wider
+        let mut hll = HyperLogLog::new(10);
+        hll.add(1, splitmix(1));
+        let (low, high) = hll.plausible_count_range();
+        assert_eq!(low, 1.0);
+        assert!(high > 3.0 && high < 5.0, "high {}", high);
+    }
+
+    #[test]
+    fn test_lazy_seeds_agree_with_dense() {
+        let mut dense = HyperLogLog::new(8);
+        let mut lazy = HyperLogLog::new_lazy_seeds(8);
+
+        for seed in 1..2_000u64 {
             let hash = splitmix(seed);
-            hll.add(seed, hash);
+            // Mix seeded and unseeded updates
+            if seed % 3 == 0 {
+                assert_eq!(dense.add_hash(hash), lazy.add_hash(hash));
+            } else {
+                assert_eq!(dense.add(seed, hash), lazy.add(seed, hash));
+            }
         }
 
-        let estimate = hll.count();
+        assert_eq!(dense.hashes(), lazy.hashes());
+        assert_eq!(dense.seeds(), lazy.seeds());
+        for register in 0..dense.hashes().len() {
+            assert_eq!(dense.seed(register), lazy.seed(register));
+        }
+        assert_eq!(dense, lazy);
+        assert_eq!(dense.count(), lazy.count());
+    }
+
+    #[test]
+    fn test_lazy_seeds_only_store_recorded_seeds() {
+        let mut hll = HyperLogLog::new_lazy_seeds(10);
+        hll.add_hash(0x10);
+        hll.add(7, 0x0400_0000_0000_0020);
+
+        let Seeds::Lazy(stored) = &hll.seeds else {
+            panic!("expected lazy seed storage");
+        };
+        assert_eq!(stored.recorded.len(), 1);
+
+        assert_eq!(hll.seeds()[0x20], 7);
+
+        // Overwriting a seeded register with an unseeded hash drops the entry,
+        // and the materialized array with it
+        assert!(hll.add_hash(0x0000_0000_0000_0020));
+        assert_eq!(hll.seed(0x20), 0);
+        assert_eq!(hll.seeds()[0x20], 0);
+        let Seeds::Lazy(stored) = &hll.seeds else {
+            panic!("expected lazy seed storage");
+        };
+        assert!(stored.recorded.is_empty());
+    }
+
+    #[test]
+    fn test_estimate_delta_if_added_matches_real_add() {
+        let mut hll = HyperLogLog::new(8);
+        for seed in 0..3_000u64 {
+            let hash = splitmix(seed);
+            let predicted = hll.estimate_delta_if_added(hash);
+            let before = hll.count();
+            let improved = hll.add(seed, hash);
+
+            assert_eq!(hll.count() - before, predicted);
+            if !improved {
+                assert_eq!(predicted, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_attributed_credits_overlapping_sources() {
+        let mut base = HyperLogLog::new(4);
+        base.add(1, 0x0010_0000_0000_0000);
+
+        let mut carol = HyperLogLog::new(4);
+        let mut dave = HyperLogLog::new(4);
+        // Register 0: dave wins outright
+        carol.add(10, 0x0100_0000_0000_0000);
+        dave.add(20, 0x0000_0100_0000_0000);
+        // Register 1: equal hashes, carol's smaller seed wins the tie
+        carol.add(5, 0x0001_0000_0000_0001);
+        dave.add(9, 0x0001_0000_0000_0001);
+        // Register 2: only dave contributes
+        dave.add(30, 0x0001_0000_0000_0002);
+        // Register 3: neither beats the base sketch
+        base.add(2, 0x0000_0000_0000_0013);
+        carol.add(11, 0x0100_0000_0000_0003);
+
+        let mut forward = base.clone();
+        let credit = forward
+            .merge_attributed(&[("carol", carol.clone()), ("dave", dave.clone())])
+            .unwrap();
+        assert_eq!(credit["carol"], 1);
+        assert_eq!(credit["dave"], 2);
+        assert_eq!(forward.seed(1), 5);
+
+        // Slice order doesn't change the outcome
+        let mut reverse = base.clone();
+        let reverse_credit = reverse
+            .merge_attributed(&[("dave", dave.clone()), ("carol", carol.clone())])
+            .unwrap();
+        assert_eq!(reverse_credit, credit);
+        assert_eq!(reverse, forward);
+
+        // Same registers as a plain merge
+        let mut plain = base.clone();
+        plain.merge_with_stats(&carol).unwrap();
+        plain.merge_with_stats(&dave).unwrap();
+        assert_eq!(plain, forward);
+    }
+
+    #[test]
+    fn test_merge_attributed_checks_bits_first() {
+        let mut base = HyperLogLog::new(4);
+        let mut good = HyperLogLog::new(4);
+        good.add(1, 0x01);
+
+        let result = base.merge_attributed(&[(1u32, good), (2u32, HyperLogLog::new(5))]);
+        assert_eq!(
+            result,
+            Err(MergeError::BitsMismatch {
+                expected: 4,
+                found: 5
+            })
+        );
+        assert_eq!(base, HyperLogLog::new(4));
+    }
+
+    #[test]
+    fn test_plausibility_score_separates_honest_from_trivially_filled() {
+        for n in [50u64, 1_000, 50_000] {
+            let mut honest = HyperLogLog::new(10);
+            for seed in 0..n {
+                honest.add(seed, splitmix(seed));
+            }
+            let score = honest.plausibility_score();
+            assert!(score > 0.8, "honest sketch of {} scored {}", n, score);
+        }
+
+        // One arbitrary hash per register: full occupancy, shallow minima
+        let mut forged = HyperLogLog::new(10);
+        for register in 0..1024u64 {
+            forged.add_hash((splitmix(register) & !0x3FF) | register);
+        }
+        let score = forged.plausibility_score();
+        assert!(score < 0.5, "forged sketch scored {}", score);
+
+        assert_eq!(HyperLogLog::new(10).plausibility_score(), 1.0);
+    }
+
+    #[test]
+    fn test_merge_stream_matches_merge_many() {
+        let sketches: Vec<HyperLogLog> = (0..8u64)
+            .map(|worker| {
+                let mut hll = HyperLogLog::new(8);
+                for i in 0..500u64 {
+                    let seed = worker * 1_000 + i;
+                    hll.add(seed, splitmix(seed));
+                }
+                hll
+            })
+            .collect();
+
+        let collected = HyperLogLog::merge_many(&sketches).unwrap();
+        let streamed = HyperLogLog::merge_stream(8, sketches.iter().cloned()).unwrap();
+        assert_eq!(streamed, collected);
+        assert_eq!(streamed.count(), collected.count());
+    }
+
+    #[test]
+    fn test_merge_stream_validates_each_item() {
+        let sketches = vec![HyperLogLog::new(8), HyperLogLog::new(6)];
+        assert_eq!(
+            HyperLogLog::merge_stream(8, sketches),
+            Err(MergeError::BitsMismatch {
+                expected: 8,
+                found: 6
+            })
+        );
+
+        let empty = HyperLogLog::merge_stream(8, Vec::new()).unwrap();
+        assert_eq!(empty, HyperLogLog::new(8));
+        assert_eq!(HyperLogLog::merge_many(&[]), Err(MergeError::Empty));
+    }
+
+    #[test]
+    fn test_assert_compatible_all_equal() {
+        let sketches: Vec<HyperLogLog> = (0..5).map(|_| HyperLogLog::new(7)).collect();
+        assert_eq!(HyperLogLog::assert_compatible(&sketches), Ok(7));
+        assert_eq!(HyperLogLog::assert_compatible(&sketches[..1]), Ok(7));
+    }
+
+    #[test]
+    fn test_assert_compatible_reports_first_mismatch() {
+        let sketches = vec![
+            HyperLogLog::new(7),
+            HyperLogLog::new(7),
+            HyperLogLog::new(9),
+            HyperLogLog::new(4),
+        ];
+        assert_eq!(
+            HyperLogLog::assert_compatible(&sketches),
+            Err(MergeError::BitsMismatch {
+                expected: 7,
+                found: 9
+            })
+        );
+        assert_eq!(HyperLogLog::assert_compatible(&[]), Err(MergeError::Empty));
+    }
+
+    #[test]
+    fn test_occupancy_by_group_detects_lopsided_fill() {
+        let mut hll = HyperLogLog::new(6);
+        // Fill every register in the lower half of the index space
+        for register in 0..32u64 {
+            hll.add(register, register);
+        }
+
+        assert_eq!(hll.occupancy_by_group(1), vec![1.0, 0.0]);
+        assert_eq!(hll.occupancy_by_group(2), vec![1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(hll.occupancy_by_group(0), vec![0.5]);
+    }
+
+    #[test]
+    fn test_occupancy_by_group_clamps_group_bits() {
+        let mut hll = HyperLogLog::new(2);
+        hll.add_hash(0x2);
+
+        // One group per register at most
+        assert_eq!(hll.occupancy_by_group(10), vec![0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_merge_reporting_work_matches_before_after() {
+        let mut server = HyperLogLog::new(8);
+        let mut upload = HyperLogLog::new(8);
+        for seed in 0..5_000u64 {
+            server.add(seed, splitmix(seed));
+        }
+        for seed in 5_000..20_000u64 {
+            upload.add(seed, splitmix(seed));
+        }
+
+        let mut expected = server.clone();
+        let before = expected.estimated_work();
+        expected.merge_with_stats(&upload).unwrap();
+        let after = expected.estimated_work();
+
+        let reported = server.merge_reporting_work(&upload).unwrap();
+        assert_eq!(reported, after - before);
+        assert!(reported > 0.0);
+        assert_eq!(server, expected);
+    }
+
+    #[test]
+    fn test_estimated_work_ci_contains_estimate_and_widens_when_sparse() {
+        let relative_width = |items: u64| {
+            let mut hll = HyperLogLog::new(10);
+            for seed in 0..items {
+                hll.add(seed, splitmix(seed));
+            }
+            let estimate = hll.estimated_work();
+            let (low, high) = hll.estimated_work_ci(Z_95);
+            assert!(low < estimate && estimate < high, "{} items", items);
 
-        // Min-hash HLL has different statistical properties than standard HLL.
-        // With 12 bits (4096 registers) and 10K items, we expect reasonable
-        // accuracy but allow 50% tolerance due to the min-hash variant behavior.
-        let lower = n as f64 * 0.5;
-        let upper = n as f64 * 1.5;
+            // A wider z gives a wider interval around the same estimate
+            let (wide_low, wide_high) = hll.estimated_work_ci(3.0);
+            assert!(wide_low < low && high < wide_high);
+            assert_eq!(hll.estimated_work_ci(-3.0), (wide_low, wide_high));
+            (high - low) / estimate
+        };
+
+        let sparse = relative_width(200);
+        let medium = relative_width(2_000);
+        let dense = relative_width(100_000);
         assert!(
-            estimate > lower && estimate < upper,
-            "Expected estimate near {}, got {}",
-            n,
-            estimate
+            sparse > medium && medium > dense,
+            "{} {} {}",
+            sparse,
+            medium,
+            dense
         );
+        // Fully occupied, the width approaches 2 * z / sqrt(m)
+        assert!((dense - 2.0 * Z_95 / 32.0).abs() < 0.01, "{}", dense);
+
+        let mut hll = HyperLogLog::new(12);
+        for seed in 0..100_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let (low, high) = hll.estimated_work_ci(Z_95);
+        assert!(low < 100_000.0 && 100_000.0 < high, "{} {}", low, high);
+
+        assert_eq!(HyperLogLog::new(10).estimated_work_ci(Z_95), (0.0, 0.0));
     }
 
     #[test]
-    fn test_count_handles_single_item() {
+    fn test_estimated_work_tracks_attempts() {
+        assert_eq!(HyperLogLog::new(12).estimated_work(), 0.0);
+
+        // With most registers filled the estimator's standard error is about
+        // 1 / sqrt(2^bits), roughly 1.6% at 12 bits
         let mut hll = HyperLogLog::new(12);
-        let hash = splitmix(0);
-        hll.add(0, hash);
+        let n = 100_000u64;
+        for seed in 0..n {
+            hll.add(seed, splitmix(seed));
+        }
 
-        let estimate = hll.count();
-        // With min-hash HLL, a single item (especially one with few leading zeros)
-        // produces a high estimate because most registers are still at u64::MAX.
-        // The estimate decreases as we find more (and lower) hashes.
-        assert!(estimate > 0.0, "Estimate should be positive");
+        let work = hll.estimated_work();
+        assert!(
+            (work - n as f64).abs() < n as f64 * 0.05,
+            "Expected work near {}, got {}",
+            n,
+            work
+        );
     }
 
     #[test]
-    fn test_json_roundtrip() {
+    fn test_merge_reporting_work_rejects_bits_mismatch() {
+        let mut server = HyperLogLog::new(8);
+        let upload = HyperLogLog::new(6);
+        assert!(server.merge_reporting_work(&upload).is_err());
+        assert_eq!(server.estimated_work(), 0.0);
+    }
+
+    #[test]
+    fn test_iter_covers_every_register() {
+        let mut hll = HyperLogLog::new(3);
+        hll.add(11, 0x0000_0000_0000_0102); // register 2
+        hll.add(22, 0x0000_0000_0000_0107); // register 7
+
+        let registers: Vec<(usize, u64, u64)> = hll.iter().collect();
+        assert_eq!(registers.len(), 8);
+        for (position, &(index, hash, seed)) in registers.iter().enumerate() {
+            assert_eq!(index, position);
+            assert_eq!(hash, hll.hashes()[index]);
+            assert_eq!(seed, hll.seeds()[index]);
+        }
+        assert_eq!(registers[2], (2, 0x102, 11));
+        assert_eq!(registers[7], (7, 0x107, 22));
+        assert_eq!(registers[0], (0, u64::MAX, 0));
+    }
+
+    #[test]
+    fn test_registers_line_up_with_slices() {
+        for mut hll in [HyperLogLog::new(3), HyperLogLog::new_lazy_seeds(3)] {
+            hll.add(11, 0x0000_0000_0000_0102); // register 2
+            hll.add(22, 0x0000_0000_0000_0107); // register 7
+            hll.add_hash(0x0000_0000_0000_0204); // register 4, no seed
+
+            let registers: Vec<Register> = hll.registers().collect();
+            assert_eq!(registers.len(), 8);
+            for (position, register) in registers.iter().enumerate() {
+                assert_eq!(register.index, position);
+                assert_eq!(register.hash, hll.hashes()[position]);
+                assert_eq!(register.seed, hll.seeds()[position]);
+                assert_eq!(register.is_empty(), ![2, 4, 7].contains(&position));
+            }
+            assert_eq!(
+                registers[4],
+                Register {
+                    index: 4,
+                    hash: 0x204,
+                    seed: 0
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_top_k_registers_orders_by_leading_zeros() {
         let mut hll = HyperLogLog::new(4);
-        hll.add(1, 0x1234);
-        hll.add(2, 0x5678_0002);
+        // (seed, leading zeros above the 4 register bits) for registers 0..8
+        let zeros = [5u32, 40, 0, 17, 59, 17, 33, 2];
+        for (register, &z) in zeros.iter().enumerate() {
+            let depth = (1u64 << 59) >> z;
+            // Register 5 holds a slightly lower hash than register 3
+            let extra = if register == 5 { 0 } else { 1 };
+            let hash = ((depth | extra) << 4) | register as u64;
+            hll.add(100 + register as u64, hash);
+            assert_eq!(HyperLogLog::rho(4, hash), z + 1);
+        }
 
-        let json = hll.to_json();
-        let restored = HyperLogLog::from_json(4, &json);
+        let top = hll.top_k_registers(4);
+        let indices: Vec<usize> = top.iter().map(|r| r.index).collect();
+        assert_eq!(indices, [4, 1, 6, 5]);
+        for register in &top {
+            assert_eq!(register.seed, 100 + register.index as u64);
+            assert_eq!(register.hash, hll.hashes()[register.index]);
+        }
 
-        assert_eq!(hll.hashes(), restored.hashes());
-        // Seeds are not preserved in JSON
-        assert_eq!(restored.seeds(), &[0u64; 16]);
+        // Asking for more than is filled returns every filled register
+        let all: Vec<usize> = hll.top_k_registers(100).iter().map(|r| r.index).collect();
+        assert_eq!(all, [4, 1, 6, 5, 3, 0, 7, 2]);
+        assert!(hll.top_k_registers(0).is_empty());
+        assert!(HyperLogLog::new(4).top_k_registers(3).is_empty());
     }
 
     #[test]
-    fn test_json_handles_invalid_input() {
-        // Invalid JSON returns default state
-        let hll = HyperLogLog::from_json(4, "not valid json");
-        assert_eq!(hll.hashes().len(), 16);
-        assert!(hll.hashes().iter().all(|&h| h == u64::MAX));
+    fn test_probabilistic_verify_passes_honest_sketch() {
+        let mut hll = HyperLogLog::new(8);
+        for seed in 0..5_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+
+        let report = hll.probabilistic_verify(0.1, splitmix);
+        let filled = hll.filled_registers();
+        assert_eq!(report.checked, (filled as f64 * 0.1).ceil() as usize);
+        assert_eq!(report.passed, report.checked);
+        assert!(report.failed_registers.is_empty());
+        assert_eq!(report.confidence, report.checked as f64 / filled as f64);
+
+        // Deterministic for the same sketch
+        assert_eq!(report, hll.probabilistic_verify(0.1, splitmix));
+
+        let full = hll.probabilistic_verify(1.0, splitmix);
+        assert_eq!(full.checked, filled);
+        assert_eq!(full.confidence, 1.0);
     }
 
     #[test]
-    fn test_json_handles_partial_data() {
-        // Fewer values than registers
-        let json = r#"["100", "200"]"#;
-        let hll = HyperLogLog::from_json(4, json); // 16 registers
+    fn test_probabilistic_verify_catches_tampered_register() {
+        let trials = 200u64;
+        let mut caught = 0;
 
-        assert_eq!(hll.hashes()[0], 100);
-        assert_eq!(hll.hashes()[1], 200);
-        assert_eq!(hll.hashes()[2], u64::MAX);
+        for trial in 0..trials {
+            let mut hll = HyperLogLog::new(6);
+            for seed in 0..1_000u64 {
+                let seed = seed + trial * 1_000_000;
+                hll.add(seed, splitmix(seed));
+            }
+            // Forge one register with a seed that doesn't reproduce its hash
+            let target = (trial as usize * 7) % 64;
+            hll.seeds.set(target, hll.seed(target) ^ 1);
+
+            let report = hll.probabilistic_verify(0.9, splitmix);
+            if report.failed_registers == vec![target] {
+                caught += 1;
+                assert_eq!(report.confidence, 0.0);
+            } else {
+                assert!(report.failed_registers.is_empty());
+            }
+        }
+
+        // Each trial should catch the forgery with ~90% probability
+        assert!(caught >= 160, "caught only {} of {}", caught, trials);
+    }
+
+    #[test]
+    fn test_probabilistic_verify_empty_sketch() {
+        let report = HyperLogLog::new(4).probabilistic_verify(0.5, splitmix);
+        assert_eq!(report.checked, 0);
+        assert_eq!(report.confidence, 1.0);
     }
 
     #[test]
@@ -504,6 +5489,33 @@ mod tests {
         assert_eq!(hll.hashes().len(), 1_048_576);
     }
 
+    #[test]
+    fn test_debug_dump_reports_key_fields() {
+        let mut hll = HyperLogLog::new(4);
+        hll.add(7, 0x0000_0000_0000_0013);
+        hll.add(9, 0xFFFF_0000_0000_0005);
+
+        let report = hll.debug_dump();
+        assert!(report.contains("bits=4"));
+        assert!(report.contains("occupancy: 2/16"));
+        assert!(report.contains("rho: min="));
+        assert!(report.contains("deepest registers:"));
+        assert!(report.contains("seed=7"));
+        assert!(report.contains("seed=9"));
+        assert!(report.contains(&format!("estimate: {:.2}", hll.count())));
+
+        // The deeper register (seed 7) is listed before the shallow one
+        assert!(report.find("seed=7").unwrap() < report.find("seed=9").unwrap());
+    }
+
+    #[test]
+    fn test_debug_dump_empty_sketch() {
+        let report = HyperLogLog::new(4).debug_dump();
+        assert!(report.contains("occupancy: 0/16"));
+        assert!(report.contains("rho: n/a"));
+        assert!(report.contains("estimate: 0.00"));
+    }
+
     #[test]
     fn test_leading_zeros_safe_for_edge_cases() {
         let mut hll = HyperLogLog::new(12);
@@ -517,4 +5529,68 @@ mod tests {
         // Should not panic
         let _ = hll.count();
     }
+
+    #[test]
+    fn test_rho_table() {
+        // (bits, hash, expected rho)
+        let cases: &[(u8, u64, u32)] = &[
+            (1, 0, 64),
+            (1, 1 << 1, 63),
+            (1, 1 << 62, 2),
+            (1, 1 << 63, 1),
+            (4, 0, 61),
+            (4, 0xF, 61),
+            (4, 1 << 4, 60),
+            (4, 1 << 62, 2),
+            (4, 1 << 63, 1),
+            (4, u64::MAX - 1, 1),
+            (12, 0, 53),
+            (12, 0xFFF, 53),
+            (12, 1 << 12, 52),
+            (12, 1 << 13, 51),
+            (12, 1 << 62, 2),
+            (12, 1 << 63, 1),
+            (MAX_HLL_BITS, 0, 45),
+            (MAX_HLL_BITS, 1 << MAX_HLL_BITS, 44),
+            (MAX_HLL_BITS, 1 << 63, 1),
+        ];
+
+        for &(bits, hash, expected) in cases {
+            assert_eq!(
+                HyperLogLog::rho(bits, hash),
+                expected,
+                "rho(bits={}, hash={:#x})",
+                bits,
+                hash
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_rho_is_deepest_achievable() {
+        for bits in 1..=MAX_HLL_BITS {
+            let hll = HyperLogLog::new(bits);
+            assert_eq!(hll.hash_bit_width(), 64);
+            assert_eq!(hll.max_rho(), HyperLogLog::rho(bits, 0));
+            // Any nonzero remainder is strictly shallower
+            assert_eq!(hll.max_rho() - 1, HyperLogLog::rho(bits, 1 << bits));
+        }
+    }
+
+    #[test]
+    fn test_rho_ignores_register_bits_and_clamps_width() {
+        for bits in [1u8, 4, 12, MAX_HLL_BITS] {
+            let mask = (1u64 << bits) - 1;
+            let upper = 1u64 << 40;
+            assert_eq!(
+                HyperLogLog::rho(bits, upper),
+                HyperLogLog::rho(bits, upper | mask)
+            );
+            // Never exceeds the remaining width plus one
+            assert!(HyperLogLog::rho(bits, 0) <= 65 - bits as u32);
+        }
+
+        assert_eq!(HyperLogLog::rho(0, 0), HyperLogLog::rho(1, 0));
+        assert_eq!(HyperLogLog::rho(64, 0), HyperLogLog::rho(MAX_HLL_BITS, 0));
+    }
 }
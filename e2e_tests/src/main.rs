@@ -53,6 +53,31 @@ async fn main() -> Result<()> {
 }
 
 pub async fn run(driver: &WebDriver, webapp_url: &str) -> Result<()> {
+    // Ensuring the schema must succeed on the fresh local database and again
+    // once it is already up to date. Requests are made from a same-origin
+    // page that doesn't touch D1, as the app itself creates the schema.
+    if let Ok(admin_token) = std::env::var("E2E_ADMIN_TOKEN") {
+        driver
+            .goto(format!("{}/api/wasm/e2e-missing.wasm", webapp_url))
+            .await?;
+        for attempt in ["empty", "up-to-date"] {
+            println!("Ensuring schema on {} database...", attempt);
+            let (status, body) = ensure_schema(driver, &admin_token).await?;
+            assert_eq!(status, 200, "ensure-schema should succeed: {}", body);
+            assert_eq!(
+                body["ok"].as_bool(),
+                Some(true),
+                "ensure-schema should report ok: {}",
+                body
+            );
+            assert!(
+                body["schema_version"].as_u64().is_some_and(|v| v > 0),
+                "ensure-schema should report the schema version: {}",
+                body
+            );
+        }
+    }
+
     println!("Navigating to home page...");
     driver.goto(webapp_url).await?;
     tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
@@ -77,7 +102,80 @@ pub async fn run(driver: &WebDriver, webapp_url: &str) -> Result<()> {
         );
     }
 
+    // Polled aggregates are served from a snapshot, so a burst of requests
+    // shares one computation.
+    println!("Requesting the repository listing twice...");
+    let no_headers = serde_json::json!({});
+    let first = fetch_from_page(driver, "GET", "/api/repositories", no_headers.clone()).await?;
+    let second = fetch_from_page(driver, "GET", "/api/repositories", no_headers).await?;
+    assert_eq!(first.status, 200, "listing should succeed: {}", first.body);
+    assert_eq!(
+        second.status, 200,
+        "listing should succeed: {}",
+        second.body
+    );
+    assert_eq!(
+        second.body, first.body,
+        "a rapid second request should return the cached listing"
+    );
+    let age = second
+        .cache_age
+        .as_deref()
+        .and_then(|age| age.parse::<u64>().ok());
+    assert!(
+        age.is_some_and(|age| age < 10),
+        "cached listing should report its age within the staleness bound, got {:?}",
+        second.cache_age
+    );
+
     println!("All tests passed!");
 
     Ok(())
 }
+
+/// Response to a request made from the page with [`fetch_from_page`]
+struct PageResponse {
+    status: u64,
+    cache_age: Option<String>,
+    body: String,
+}
+
+/// Fetch `path` from the page, so requests share its origin and cookies.
+async fn fetch_from_page(
+    driver: &WebDriver,
+    method: &str,
+    path: &str,
+    headers: serde_json::Value,
+) -> Result<PageResponse> {
+    let script = format!(
+        r#"
+        const done = arguments[arguments.length - 1];
+        fetch({}, {{ method: {}, headers: {} }})
+            .then(async (response) => done([
+                response.status,
+                response.headers.get("X-Cache-Age"),
+                await response.text(),
+            ]))
+            .catch((err) => done([0, null, String(err)]));
+        "#,
+        serde_json::Value::from(path),
+        serde_json::Value::from(method),
+        headers
+    );
+    let ret = driver.execute_async(script, Vec::new()).await?;
+    let ret = ret.json();
+    Ok(PageResponse {
+        status: ret[0].as_u64().unwrap_or(0),
+        cache_age: ret[1].as_str().map(str::to_string),
+        body: ret[2].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// POST `/api/admin/ensure-schema` from the page, returning the status and
+/// parsed JSON body.
+async fn ensure_schema(driver: &WebDriver, admin_token: &str) -> Result<(u64, serde_json::Value)> {
+    let headers = serde_json::json!({ "Authorization": format!("Bearer {}", admin_token) });
+    let response = fetch_from_page(driver, "POST", "/api/admin/ensure-schema", headers).await?;
+    let body = serde_json::from_str(&response.body).unwrap_or(serde_json::Value::Null);
+    Ok((response.status, body))
+}
@@ -10,6 +10,7 @@ set -e
 #   WRANGLER_PORT (optional, default: 8787)
 #   WEBDRIVER_PORT (optional, default: 4444)
 #   E2E_BROWSER (optional, default: firefox)
+#   E2E_ADMIN_TOKEN (optional, default: e2e-admin-token)
 
 # shellcheck disable=SC2153
 : "${WEBAPP_PATH:?}" "${CURL_BIN:?}" "${WRANGLER_BIN:?}" "${E2E_TESTS_BIN:?}"
@@ -64,7 +65,9 @@ done
 # Start wrangler dev with e2e environment
 echo "Starting wrangler dev with e2e environment..."
 WRANGLER_PORT=${WRANGLER_PORT:-8787}
-"$WRANGLER_BIN" dev --env e2e --port "$WRANGLER_PORT" > wrangler.log 2>&1 &
+# Admin endpoints need a token; the e2e run uses a throwaway one
+E2E_ADMIN_TOKEN=${E2E_ADMIN_TOKEN:-e2e-admin-token}
+"$WRANGLER_BIN" dev --env e2e --port "$WRANGLER_PORT" --var "ADMIN_TOKEN:$E2E_ADMIN_TOKEN" > wrangler.log 2>&1 &
 WRANGLER_PID=$!
 echo "Wrangler started with PID $WRANGLER_PID"
 
@@ -88,6 +91,7 @@ done
 echo "Running e2e tests with headless Firefox..."
 export WRANGLER_PORT
 export WEBDRIVER_PORT
+export E2E_ADMIN_TOKEN
 export E2E_BROWSER=${E2E_BROWSER:-firefox}
 
 # Run tests and capture exit code
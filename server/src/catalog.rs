@@ -37,10 +37,26 @@ const REPO_PREFIX: &str = "repo:";
 const VERSION_PREFIX: &str = "version:";
 const REPOS_LIST_KEY: &str = "repos:list";
 const CI_JTI_PREFIX: &str = "ci-jti:";
+const CACHE_PREFIX: &str = "cache:";
+const VERIFY_PREFIX: &str = "verify:";
 
 /// Replay protection TTL in seconds (10 minutes)
 const REPLAY_TTL_SECS: u64 = 600;
 
+/// Minimum time between proof re-verifications of the same function (1 minute)
+const VERIFY_COOLDOWN_SECS: u64 = 60;
+
+/// KV refuses expirations shorter than 60 seconds, so cached aggregates are
+/// kept at least this long and freshness is checked against `computed_at`
+const CACHE_MIN_TTL_SECS: u64 = 60;
+
+/// Cached response body for an expensive aggregate endpoint
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CachedAggregate {
+    pub computed_at: u64,
+    pub body: String,
+}
+
 /// Get list of all repository names
 pub async fn list_repos(kv: &KvStore) -> Result<Vec<String>> {
     let value = kv.get(REPOS_LIST_KEY).text().await?;
@@ -146,3 +162,48 @@ pub async fn check_and_mark_replay(kv: &KvStore, jti_hash: &str) -> Result<bool>
 
     Ok(false)
 }
+
+/// Get the cached body of the aggregate `name`, if any
+pub async fn get_cached_aggregate(kv: &KvStore, name: &str) -> Result<Option<CachedAggregate>> {
+    let key = format!("{}{}", CACHE_PREFIX, name);
+    let value = kv.get(&key).text().await?;
+    Ok(value.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+/// Store the body of the aggregate `name` for reuse by subsequent requests,
+/// keeping it for at least `max_age_secs`
+pub async fn put_cached_aggregate(
+    kv: &KvStore,
+    name: &str,
+    cached: &CachedAggregate,
+    max_age_secs: u64,
+) -> Result<()> {
+    let key = format!("{}{}", CACHE_PREFIX, name);
+    let json = serde_json::to_string(cached)?;
+    kv.put(&key, json)?
+        .expiration_ttl(max_age_secs.max(CACHE_MIN_TTL_SECS))
+        .execute()
+        .await?;
+    Ok(())
+}
+
+/// Check and mark a proof re-verification of a function
+/// Returns true if one already ran within the cooldown (rate limited)
+pub async fn check_and_mark_verify(
+    kv: &KvStore,
+    r2_key: &str,
+    function_name: &str,
+) -> Result<bool> {
+    let key = format!("{}{}:{}", VERIFY_PREFIX, r2_key, function_name);
+
+    if kv.get(&key).text().await?.is_some() {
+        return Ok(true);
+    }
+
+    kv.put(&key, "1")?
+        .expiration_ttl(VERIFY_COOLDOWN_SECS)
+        .execute()
+        .await?;
+
+    Ok(false)
+}
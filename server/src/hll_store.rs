@@ -3,12 +3,24 @@
 //! Stores HyperLogLog register values with atomic updates.
 //! Each register is stored as a separate row to enable lock-free atomic updates.
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use hyperloglog::{HyperLogLog, DEFAULT_HLL_BITS};
-use worker::{d1::D1Database, Result};
+use wasm_bindgen::JsValue;
+use worker::{
+    d1::{D1Database, D1PreparedStatement, D1Result},
+    Result,
+};
 
 /// Maximum value for u64, used as initial hash value
 const U64_MAX_STR: &str = "18446744073709551615";
 
+/// Version of the schema created by `ensure_schema`, reported to CI
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Register width idle functions are folded down to by [`compact_inactive_functions`]
+pub const COMPACTED_HLL_BITS: u8 = 3;
+
 /// Format a u64 as a zero-padded 20-character string for correct lexicographic comparison
 pub fn format_hash(hash: u64) -> String {
     format!("{:020}", hash)
@@ -19,7 +31,27 @@ pub fn parse_hash(s: &str) -> u64 {
     s.parse().unwrap_or(u64::MAX)
 }
 
+/// Set once [`ensure_schema`] has succeeded in this isolate
+static SCHEMA_READY: AtomicBool = AtomicBool::new(false);
+
+/// Run [`ensure_schema`] once per isolate
+///
+/// Read and submit paths call this before touching tables added after the
+/// first deployment, so an old database is upgraded by whichever request
+/// reaches it first without every request paying the extra round-trips.
+pub async fn ensure_schema_once(db: &D1Database) -> Result<()> {
+    if SCHEMA_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    ensure_schema(db).await?;
+    SCHEMA_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
 /// Initialize the database schema
+///
+/// Every statement is `IF NOT EXISTS`, so this is safe to run repeatedly and
+/// concurrently, on both an empty and an up-to-date database.
 pub async fn ensure_schema(db: &D1Database) -> Result<()> {
     db.exec(
         "CREATE TABLE IF NOT EXISTS function_hashes (
@@ -37,36 +69,173 @@ pub async fn ensure_schema(db: &D1Database) -> Result<()> {
     db.exec("CREATE INDEX IF NOT EXISTS idx_hashes_by_file ON function_hashes(r2_key)")
         .await?;
 
+    // Denormalized deepest proof per function, so leaderboards don't scan registers
+    db.exec(
+        "CREATE TABLE IF NOT EXISTS function_records (
+            r2_key TEXT NOT NULL,
+            function_name TEXT NOT NULL,
+            lowest_hash TEXT NOT NULL,
+            lowest_seed TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (r2_key, function_name)
+        )",
+    )
+    .await?;
+
+    // Functions whose registers were folded to fewer bits; absent means
+    // the registers are stored at `DEFAULT_HLL_BITS`
+    db.exec(
+        "CREATE TABLE IF NOT EXISTS function_compactions (
+            r2_key TEXT NOT NULL,
+            function_name TEXT NOT NULL,
+            bits INTEGER NOT NULL,
+            compacted_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (r2_key, function_name)
+        )",
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Rewrite register rows stored under older conventions into the canonical shape
+///
+/// Legacy writers left hashes without zero padding (which breaks the
+/// lexicographic compare-and-set), non-numeric hashes, registers indexed for
+/// a different bit width, and filled registers without a seed. Each statement
+/// only matches rows that are still non-canonical, so the migration is
+/// idempotent and an interrupted run is resumed by running it again.
+pub async fn migrate_legacy_registers(db: &D1Database) -> Result<()> {
+    let num_registers = 1i64 << DEFAULT_HLL_BITS;
+
+    let statements = vec![
+        // Unparseable hashes become empty registers
+        db.prepare(
+            "UPDATE function_hashes SET min_hash = ?, seed = NULL
+             WHERE min_hash = '' OR min_hash GLOB '*[^0-9]*' OR length(min_hash) > 20",
+        )
+        .bind(&[U64_MAX_STR.into()])?,
+        // Zero-pad short hashes to the fixed comparison width
+        db.prepare(
+            "UPDATE function_hashes SET min_hash = substr('00000000000000000000' || min_hash, -20)
+             WHERE length(min_hash) < 20",
+        ),
+        // A register index from a wider sketch is the hash's low bits, so
+        // the canonical register is the index modulo the register count
+        db.prepare(
+            "INSERT INTO function_hashes (r2_key, function_name, register_idx, min_hash, seed, updated_at)
+             SELECT r2_key, function_name, register_idx % ?1, min_hash, seed, datetime('now')
+             FROM function_hashes WHERE register_idx >= ?1
+             ON CONFLICT (r2_key, function_name, register_idx) DO UPDATE SET
+               seed = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.seed ELSE function_hashes.seed END,
+               updated_at = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.updated_at ELSE function_hashes.updated_at END,
+               min_hash = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.min_hash ELSE function_hashes.min_hash END",
+        )
+        .bind(&[num_registers.into()])?,
+        db.prepare("DELETE FROM function_hashes WHERE register_idx >= ? OR register_idx < 0")
+            .bind(&[num_registers.into()])?,
+        // Filled registers without a seed get the `add_hash` default
+        db.prepare("UPDATE function_hashes SET seed = '0' WHERE seed IS NULL AND min_hash < ?")
+            .bind(&[U64_MAX_STR.into()])?,
+    ];
+
+    // One batch is one transaction, so folded rows are never deleted
+    // without their values having been merged
+    db.batch(statements).await?;
+
+    Ok(())
+}
+
+/// Fill `function_records` for functions whose registers predate the table
+pub async fn backfill_function_records(db: &D1Database) -> Result<()> {
+    // SQLite returns the bare `seed` column from the row holding MIN(min_hash)
+    db.prepare(
+        "INSERT OR IGNORE INTO function_records (r2_key, function_name, lowest_hash, lowest_seed, updated_at)
+         SELECT r2_key, function_name, MIN(min_hash), seed, datetime('now')
+         FROM function_hashes
+         WHERE seed IS NOT NULL AND min_hash < ?
+         GROUP BY r2_key, function_name",
+    )
+    .bind(&[U64_MAX_STR.into()])?
+    .run()
+    .await?;
+
     Ok(())
 }
 
+/// Register width a function's rows are stored at
+///
+/// Compacted functions keep their reduced width, including for new
+/// submissions; every other function uses `DEFAULT_HLL_BITS`.
+pub async fn stored_bits(db: &D1Database, r2_key: &str, function_name: &str) -> Result<u8> {
+    let results = db
+        .prepare("SELECT bits FROM function_compactions WHERE r2_key = ? AND function_name = ?")
+        .bind(&[r2_key.into(), function_name.into()])?
+        .all()
+        .await?;
+
+    Ok(stored_width(
+        results
+            .results::<CompactionRow>()?
+            .first()
+            .map(|row| row.bits),
+    ))
+}
+
+/// Register width for a `function_compactions.bits` value, if any
+///
+/// Anything that isn't a valid reduced width reads as `DEFAULT_HLL_BITS`.
+fn stored_width(bits: Option<i64>) -> u8 {
+    match bits {
+        Some(bits) if bits > 0 && bits < i64::from(DEFAULT_HLL_BITS) => bits as u8,
+        _ => DEFAULT_HLL_BITS,
+    }
+}
+
 /// Get HLL state for a function, reconstructing from individual register rows
+///
+/// The function's compaction width is joined onto its registers, so
+/// compacted functions come back at their reduced width in one query.
 pub async fn get_hll_state(
     db: &D1Database,
     r2_key: &str,
     function_name: &str,
 ) -> Result<HyperLogLog> {
-    let stmt = db.prepare(
-        "SELECT register_idx, min_hash FROM function_hashes 
-         WHERE r2_key = ? AND function_name = ?
-         ORDER BY register_idx",
-    );
+    let results = read_state(db, r2_key, function_name)?.all().await?;
 
-    let results = stmt
-        .bind(&[r2_key.into(), function_name.into()])?
-        .all()
-        .await?;
+    hll_from_rows(results.results::<RegisterRow>()?)
+}
 
-    let mut hll = HyperLogLog::new(DEFAULT_HLL_BITS);
-    let hashes = hll.hashes_mut();
+/// Read a function's register rows with their joined compaction width
+fn read_state(db: &D1Database, r2_key: &str, function_name: &str) -> Result<D1PreparedStatement> {
+    db.prepare(
+        "SELECT h.register_idx, h.min_hash, h.seed, c.bits FROM function_hashes h
+         LEFT JOIN function_compactions c
+           ON c.r2_key = h.r2_key AND c.function_name = h.function_name
+         WHERE h.r2_key = ? AND h.function_name = ?
+         ORDER BY h.register_idx",
+    )
+    .bind(&[r2_key.into(), function_name.into()])
+}
 
-    for row in results.results::<RegisterRow>()? {
-        if (row.register_idx as usize) < hashes.len() {
-            hashes[row.register_idx as usize] = parse_hash(&row.min_hash);
+/// Rebuild a sketch from its register rows, at the width the rows report
+///
+/// Rows past the width's register count are ignored.
+fn hll_from_rows(rows: Vec<RegisterRow>) -> Result<HyperLogLog> {
+    let bits = stored_width(rows.first().and_then(|row| row.bits));
+    let num_registers = 1usize << bits;
+    let mut hashes = vec![u64::MAX; num_registers];
+    let mut seeds = vec![0; num_registers];
+    for row in rows {
+        let index = row.register_idx as usize;
+        if index < num_registers {
+            hashes[index] = parse_hash(&row.min_hash);
+            seeds[index] = row.seed.and_then(|seed| seed.parse().ok()).unwrap_or(0);
         }
     }
 
-    Ok(hll)
+    HyperLogLog::from_parts(bits, hashes, Some(seeds))
+        .map_err(|e| worker::Error::RustError(format!("Invalid stored HLL state: {}", e)))
 }
 
 /// Submit a hash update - atomically updates only if the new hash is lower
@@ -77,55 +246,298 @@ pub async fn submit_hash(
     function_name: &str,
     seed: u64,
     hash: u64,
-) -> Result<bool> {
-    // Calculate which register this hash belongs to
-    let bits = DEFAULT_HLL_BITS;
-    let mask = (1usize << bits) - 1;
-    let register_idx = (hash as usize) & mask;
+) -> Result<SubmitOutcome> {
+    // Read the register on both sides of the upsert. A batch runs as one
+    // transaction, so the pair shows exactly what this submission changed,
+    // and the record moves in the same transaction as the register. Each
+    // statement picks the register at the width stored when it runs, so a
+    // concurrent compaction can't leave this hash in a full-width row.
+    let mut statements = vec![
+        read_register(db, r2_key, function_name, hash)?,
+        upsert_register(db, r2_key, function_name, seed, hash)?,
+        read_register(db, r2_key, function_name, hash)?,
+    ];
+    statements.extend(record_statements(db, r2_key, function_name, seed, hash)?);
+    let results = db.batch(statements).await?;
 
-    let hash_str = format_hash(hash);
-    let seed_str = seed.to_string();
+    let previous = register_value(&results, 0)?.map(|(_, value)| value);
+    let (register_idx, current) = written_register(&results, 2, hash)?;
+    let improved = update_improved(previous, current, hash);
+    let new_record = record_changed(&results, 3, hash)?;
 
-    // Atomic upsert - only updates if new hash is smaller (lexicographically)
-    let stmt = db.prepare(
+    Ok(SubmitOutcome {
+        improved,
+        new_record,
+        register_idx,
+        previous,
+        current,
+    })
+}
+
+/// One improvement in a sparse batch for [`submit_register_updates`]
+#[derive(Debug)]
+pub struct RegisterUpdate {
+    pub register_idx: usize,
+    pub seed: u64,
+    pub hash: u64,
+}
+
+/// Apply a sparse batch of register improvements for one function
+///
+/// Every update, and the record update for the deepest of them, is applied
+/// in a single D1 batch, so the whole set commits as one transaction. As in
+/// [`submit_hash`], each row is picked from the hash at the width stored when
+/// the batch runs; the returned outcomes report that row, in the same order
+/// as `updates`.
+pub async fn submit_register_updates(
+    db: &D1Database,
+    r2_key: &str,
+    function_name: &str,
+    updates: &[RegisterUpdate],
+) -> Result<Vec<SubmitOutcome>> {
+    if updates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut statements = Vec::with_capacity(updates.len() * 3);
+    for update in updates {
+        statements.push(read_register(db, r2_key, function_name, update.hash)?);
+        statements.push(upsert_register(
+            db,
+            r2_key,
+            function_name,
+            update.seed,
+            update.hash,
+        )?);
+        statements.push(read_register(db, r2_key, function_name, update.hash)?);
+    }
+
+    // Only the deepest update can become the function's new record
+    let deepest = updates
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, update)| update.hash);
+    if let Some((_, update)) = deepest {
+        statements.extend(record_statements(
+            db,
+            r2_key,
+            function_name,
+            update.seed,
+            update.hash,
+        )?);
+    }
+    let results = db.batch(statements).await?;
+
+    let mut outcomes = Vec::with_capacity(updates.len());
+    for (position, update) in updates.iter().enumerate() {
+        let previous = register_value(&results, position * 3)?.map(|(_, value)| value);
+        let (register_idx, current) = written_register(&results, position * 3 + 2, update.hash)?;
+        outcomes.push(SubmitOutcome {
+            improved: update_improved(previous, current, update.hash),
+            new_record: false,
+            register_idx,
+            previous,
+            current,
+        });
+    }
+
+    if let Some((position, update)) = deepest {
+        outcomes[position].new_record = record_changed(&results, updates.len() * 3, update.hash)?;
+    }
+
+    Ok(outcomes)
+}
+
+/// Whether writing `hash` is what lowered a register from `previous` to `current`
+///
+/// Updates to the same register in one batch run in order, so only the one
+/// that actually lowered it sees `current == hash` below its `previous`.
+fn update_improved(previous: Option<u64>, current: u64, hash: u64) -> bool {
+    current == hash && previous.is_none_or(|previous| hash < previous)
+}
+
+/// SQL for the register `hash` belongs to at the function's stored width
+///
+/// Expects the statement's `?1`/`?2` to be the function's key and name, `?3`
+/// the hash's register at `DEFAULT_HLL_BITS` and `?4` `DEFAULT_HLL_BITS`
+/// itself. The width is looked up when the statement runs rather than when
+/// it is built, mirroring [`stored_width`].
+const STORED_REGISTER_SQL: &str = "?3 % (1 << COALESCE(
+           (SELECT bits FROM function_compactions
+            WHERE r2_key = ?1 AND function_name = ?2 AND bits > 0 AND bits < ?4),
+           ?4))";
+
+/// Parameters for a statement using [`STORED_REGISTER_SQL`]
+fn stored_register_params(r2_key: &str, function_name: &str, hash: u64) -> [JsValue; 4] {
+    let default_register = hash & ((1 << DEFAULT_HLL_BITS) - 1);
+    [
+        r2_key.into(),
+        function_name.into(),
+        (default_register as i64).into(),
+        i64::from(DEFAULT_HLL_BITS).into(),
+    ]
+}
+
+/// Read the stored minimum of the register `hash` belongs to
+fn read_register(
+    db: &D1Database,
+    r2_key: &str,
+    function_name: &str,
+    hash: u64,
+) -> Result<D1PreparedStatement> {
+    db.prepare(format!(
+        "SELECT register_idx, min_hash FROM function_hashes
+         WHERE r2_key = ?1 AND function_name = ?2 AND register_idx = {}",
+        STORED_REGISTER_SQL
+    ))
+    .bind(&stored_register_params(r2_key, function_name, hash))
+}
+
+/// Atomic upsert - only updates if new hash is smaller (lexicographically)
+fn upsert_register(
+    db: &D1Database,
+    r2_key: &str,
+    function_name: &str,
+    seed: u64,
+    hash: u64,
+) -> Result<D1PreparedStatement> {
+    let stmt = db.prepare(format!(
         "INSERT INTO function_hashes (r2_key, function_name, register_idx, min_hash, seed, updated_at)
-         VALUES (?, ?, ?, ?, ?, datetime('now'))
+         VALUES (?1, ?2, {}, ?5, ?6, datetime('now'))
          ON CONFLICT (r2_key, function_name, register_idx) DO UPDATE SET
            min_hash = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.min_hash ELSE function_hashes.min_hash END,
            seed = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.seed ELSE function_hashes.seed END,
            updated_at = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.updated_at ELSE function_hashes.updated_at END",
-    );
+        STORED_REGISTER_SQL
+    ));
 
+    let [r2_key, function_name, default_register, default_bits] =
+        stored_register_params(r2_key, function_name, hash);
     stmt.bind(&[
+        r2_key,
+        function_name,
+        default_register,
+        default_bits,
+        format_hash(hash).into(),
+        seed.to_string().into(),
+    ])
+}
+
+/// Register index and value from the `index`th result of a batch of [`read_register`]s
+fn register_value(results: &[D1Result], index: usize) -> Result<Option<(usize, u64)>> {
+    Ok(match results.get(index) {
+        Some(result) => result
+            .results::<MinHashRow>()?
+            .first()
+            .map(|row| (row.register_idx as usize, parse_hash(&row.min_hash))),
+        None => None,
+    })
+}
+
+/// The register a [`read_register`] after an upsert of `hash` found
+///
+/// The upsert always leaves a row, so a missing one means the batch failed.
+fn written_register(results: &[D1Result], index: usize, hash: u64) -> Result<(usize, u64)> {
+    register_value(results, index)?.ok_or_else(|| {
+        worker::Error::RustError(format!("Register for hash {} missing after upsert", hash))
+    })
+}
+
+/// Statements lowering a function's recorded deepest proof if `hash` beats it
+///
+/// The same read-upsert-read pattern as the registers, so concurrent
+/// submissions agree on which one set the record. Callers put these in the
+/// batch that upserts the register, and check them with [`record_changed`].
+fn record_statements(
+    db: &D1Database,
+    r2_key: &str,
+    function_name: &str,
+    seed: u64,
+    hash: u64,
+) -> Result<Vec<D1PreparedStatement>> {
+    let record_stmt = db.prepare(
+        "INSERT INTO function_records (r2_key, function_name, lowest_hash, lowest_seed, updated_at)
+         VALUES (?, ?, ?, ?, datetime('now'))
+         ON CONFLICT (r2_key, function_name) DO UPDATE SET
+           lowest_seed = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.lowest_seed ELSE function_records.lowest_seed END,
+           updated_at = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.updated_at ELSE function_records.updated_at END,
+           lowest_hash = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.lowest_hash ELSE function_records.lowest_hash END",
+    );
+
+    let upsert = record_stmt.bind(&[
         r2_key.into(),
         function_name.into(),
-        (register_idx as i64).into(),
-        hash_str.clone().into(),
-        seed_str.into(),
-    ])?
-    .run()
-    .await?;
+        format_hash(hash).into(),
+        seed.to_string().into(),
+    ])?;
 
-    // Check if we actually improved (read back the current value)
-    let check_stmt = db.prepare(
-        "SELECT min_hash FROM function_hashes WHERE r2_key = ? AND function_name = ? AND register_idx = ?",
-    );
-    let results = check_stmt
-        .bind(&[
-            r2_key.into(),
-            function_name.into(),
-            (register_idx as i64).into(),
-        ])?
-        .all()
-        .await?;
+    let read_record = || {
+        db.prepare(
+            "SELECT lowest_hash FROM function_records WHERE r2_key = ? AND function_name = ?",
+        )
+        .bind(&[r2_key.into(), function_name.into()])
+    };
+
+    Ok(vec![read_record()?, upsert, read_record()?])
+}
+
+/// Whether the [`record_statements`] starting at `index` in a batch made
+/// `hash` the function's new record
+fn record_changed(results: &[D1Result], index: usize, hash: u64) -> Result<bool> {
+    let record_value = |index: usize| -> Result<Option<u64>> {
+        Ok(match results.get(index) {
+            Some(result) => result
+                .results::<LowestHashRow>()?
+                .first()
+                .map(|row| parse_hash(&row.lowest_hash)),
+            None => None,
+        })
+    };
+    let previous = record_value(index)?;
+    let current = record_value(index + 2)?;
 
-    let improved = results
-        .results::<MinHashRow>()?
-        .first()
-        .map(|row| row.min_hash == hash_str)
-        .unwrap_or(false);
+    Ok(current.is_some_and(|current| update_improved(previous, current, hash)))
+}
+
+/// Result of a single [`submit_hash`] call
+pub struct SubmitOutcome {
+    pub improved: bool,
+    /// Whether this hash became the function's deepest recorded proof
+    pub new_record: bool,
+    pub register_idx: usize,
+    /// Register value before the upsert, `None` if the row didn't exist yet
+    pub previous: Option<u64>,
+    /// Register value after the upsert
+    pub current: u64,
+}
 
-    Ok(improved)
+/// Deepest proof recorded for a function
+pub struct FunctionRecord {
+    pub function_name: String,
+    pub lowest_hash: u64,
+    pub lowest_seed: u64,
+}
+
+/// Get the deepest recorded proof for every function in a file
+///
+/// Ensures the schema first, since catalog reads can be the first request
+/// to reach a database created before `function_records` existed.
+pub async fn get_file_records(db: &D1Database, r2_key: &str) -> Result<Vec<FunctionRecord>> {
+    ensure_schema_once(db).await?;
+    let stmt = db.prepare(
+        "SELECT function_name, lowest_hash, lowest_seed FROM function_records WHERE r2_key = ?",
+    );
+    let results = stmt.bind(&[r2_key.into()])?.all().await?;
+
+    Ok(results
+        .results::<FunctionRecordRow>()?
+        .into_iter()
+        .map(|row| FunctionRecord {
+            function_name: row.function_name,
+            lowest_hash: parse_hash(&row.lowest_hash),
+            lowest_seed: row.lowest_seed.parse().unwrap_or(0),
+        })
+        .collect())
 }
 
 /// Initialize HLL registers for a new function (all set to MAX)
@@ -155,6 +567,234 @@ pub async fn init_function_registers(
     Ok(())
 }
 
+/// Carry a function's registers and record over to a renamed export
+///
+/// Copies every filled register of `from_name` in `from_key` into `to_name`
+/// in `to_key`, keeping the lower hash where the target already has one, and
+/// does the same for the deepest-proof record. A compacted source stays
+/// compacted under its new name, since its rows are indexed at the reduced
+/// width; the target's own rows past that width (such as the empty ones from
+/// [`init_function_registers`]) are folded in and deleted. All statements run
+/// in one batch, so a failed migration leaves the target untouched.
+pub async fn migrate_function_history(
+    db: &D1Database,
+    from_key: &str,
+    from_name: &str,
+    to_key: &str,
+    to_name: &str,
+) -> Result<()> {
+    let statements = vec![
+        db.prepare(
+            "INSERT INTO function_hashes (r2_key, function_name, register_idx, min_hash, seed, updated_at)
+             SELECT ?1, ?2, register_idx, min_hash, seed, datetime('now')
+             FROM function_hashes WHERE r2_key = ?3 AND function_name = ?4 AND min_hash < ?5
+             ON CONFLICT (r2_key, function_name, register_idx) DO UPDATE SET
+               seed = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.seed ELSE function_hashes.seed END,
+               updated_at = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.updated_at ELSE function_hashes.updated_at END,
+               min_hash = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.min_hash ELSE function_hashes.min_hash END",
+        )
+        .bind(&[
+            to_key.into(),
+            to_name.into(),
+            from_key.into(),
+            from_name.into(),
+            U64_MAX_STR.into(),
+        ])?,
+        db.prepare(
+            "INSERT INTO function_records (r2_key, function_name, lowest_hash, lowest_seed, updated_at)
+             SELECT ?1, ?2, lowest_hash, lowest_seed, datetime('now')
+             FROM function_records WHERE r2_key = ?3 AND function_name = ?4
+             ON CONFLICT (r2_key, function_name) DO UPDATE SET
+               lowest_seed = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.lowest_seed ELSE function_records.lowest_seed END,
+               updated_at = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.updated_at ELSE function_records.updated_at END,
+               lowest_hash = CASE WHEN excluded.lowest_hash < function_records.lowest_hash THEN excluded.lowest_hash ELSE function_records.lowest_hash END",
+        )
+        .bind(&[
+            to_key.into(),
+            to_name.into(),
+            from_key.into(),
+            from_name.into(),
+        ])?,
+        // Fold the target's rows past a compacted source's width, then drop them
+        db.prepare(
+            "INSERT INTO function_hashes (r2_key, function_name, register_idx, min_hash, seed, updated_at)
+             SELECT h.r2_key, h.function_name, h.register_idx % (1 << c.bits), h.min_hash, h.seed, h.updated_at
+             FROM function_hashes h
+             JOIN function_compactions c ON c.r2_key = ?3 AND c.function_name = ?4
+             WHERE h.r2_key = ?1 AND h.function_name = ?2 AND c.bits > 0 AND c.bits < ?5
+               AND h.register_idx >= (1 << c.bits) AND h.min_hash < ?6
+             ON CONFLICT (r2_key, function_name, register_idx) DO UPDATE SET
+               seed = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.seed ELSE function_hashes.seed END,
+               updated_at = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.updated_at ELSE function_hashes.updated_at END,
+               min_hash = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.min_hash ELSE function_hashes.min_hash END",
+        )
+        .bind(&[
+            to_key.into(),
+            to_name.into(),
+            from_key.into(),
+            from_name.into(),
+            i64::from(DEFAULT_HLL_BITS).into(),
+            U64_MAX_STR.into(),
+        ])?,
+        db.prepare(
+            "DELETE FROM function_hashes WHERE r2_key = ?1 AND function_name = ?2
+               AND register_idx >= (1 << (SELECT bits FROM function_compactions
+                 WHERE r2_key = ?3 AND function_name = ?4 AND bits > 0 AND bits < ?5))",
+        )
+        .bind(&[
+            to_key.into(),
+            to_name.into(),
+            from_key.into(),
+            from_name.into(),
+            i64::from(DEFAULT_HLL_BITS).into(),
+        ])?,
+        db.prepare(
+            "INSERT OR REPLACE INTO function_compactions (r2_key, function_name, bits, compacted_at)
+             SELECT ?1, ?2, bits, compacted_at
+             FROM function_compactions WHERE r2_key = ?3 AND function_name = ?4",
+        )
+        .bind(&[
+            to_key.into(),
+            to_name.into(),
+            from_key.into(),
+            from_name.into(),
+        ])?,
+    ];
+
+    db.batch(statements).await?;
+
+    Ok(())
+}
+
+/// A function folded by [`compact_inactive_functions`]
+pub struct CompactedFunction {
+    pub r2_key: String,
+    pub function_name: String,
+    /// Register rows stored before and after folding
+    pub rows_before: usize,
+    pub rows_after: usize,
+    /// Cardinality estimate before and after folding
+    pub estimate_before: f64,
+    pub estimate_after: f64,
+}
+
+/// Fold the registers of functions idle for `idle_days` down to `bits`
+///
+/// A function is idle when none of its registers has improved within the
+/// window. Its rows are folded in SQL, each `register_idx % 2^bits` keeping
+/// the lowest hash and its seed as [`HyperLogLog::fold`] would, and the
+/// full-width rows are deleted in the same batch that marks it compacted.
+/// The batch only applies while the function's latest `updated_at` still
+/// matches what the idle scan saw, so a submission landing in between keeps
+/// the function at full width. Compaction is one-way: later submissions are
+/// stored at the reduced width, trading the estimate's precision (relative
+/// error grows from about 18% at 5 bits to 37% at 3) for a 4x smaller
+/// footprint. `bits` at or above `DEFAULT_HLL_BITS` compacts nothing.
+pub async fn compact_inactive_functions(
+    db: &D1Database,
+    idle_days: u32,
+    bits: u8,
+) -> Result<Vec<CompactedFunction>> {
+    if bits == 0 || bits >= DEFAULT_HLL_BITS {
+        return Ok(Vec::new());
+    }
+
+    let stmt = db.prepare(
+        "SELECT h.r2_key, h.function_name, MAX(h.updated_at) AS last_updated
+         FROM function_hashes h
+         LEFT JOIN function_compactions c
+           ON c.r2_key = h.r2_key AND c.function_name = h.function_name
+         WHERE c.r2_key IS NULL
+         GROUP BY h.r2_key, h.function_name
+         HAVING MAX(h.updated_at) < datetime('now', ?)",
+    );
+    let idle = stmt
+        .bind(&[format!("-{} days", idle_days).into()])?
+        .all()
+        .await?
+        .results::<IdleFunctionRow>()?;
+
+    let mut compacted = Vec::with_capacity(idle.len());
+    for row in idle {
+        let params: [JsValue; 3] = [
+            row.r2_key.as_str().into(),
+            row.function_name.as_str().into(),
+            i64::from(bits).into(),
+        ];
+        let statements = vec![
+            read_state(db, &row.r2_key, &row.function_name)?,
+            // Mark the function compacted only if it is still idle
+            db.prepare(
+                "INSERT OR IGNORE INTO function_compactions (r2_key, function_name, bits, compacted_at)
+                 SELECT ?1, ?2, ?3, datetime('now')
+                 WHERE (SELECT MAX(updated_at) FROM function_hashes
+                        WHERE r2_key = ?1 AND function_name = ?2) = ?4",
+            )
+            .bind(&[
+                params[0].clone(),
+                params[1].clone(),
+                params[2].clone(),
+                row.last_updated.as_str().into(),
+            ])?,
+            // Fold the wide rows into the low ones, keeping each row's
+            // `updated_at` so the function still reads as idle
+            db.prepare(
+                "INSERT INTO function_hashes (r2_key, function_name, register_idx, min_hash, seed, updated_at)
+                 SELECT r2_key, function_name, register_idx % (1 << ?3), MIN(min_hash), seed, updated_at
+                 FROM function_hashes
+                 WHERE r2_key = ?1 AND function_name = ?2 AND register_idx >= (1 << ?3)
+                   AND EXISTS (SELECT 1 FROM function_compactions
+                               WHERE r2_key = ?1 AND function_name = ?2 AND bits = ?3)
+                 GROUP BY register_idx % (1 << ?3)
+                 ON CONFLICT (r2_key, function_name, register_idx) DO UPDATE SET
+                   seed = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.seed ELSE function_hashes.seed END,
+                   updated_at = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.updated_at ELSE function_hashes.updated_at END,
+                   min_hash = CASE WHEN excluded.min_hash < function_hashes.min_hash THEN excluded.min_hash ELSE function_hashes.min_hash END",
+            )
+            .bind(&params)?,
+            db.prepare(
+                "DELETE FROM function_hashes
+                 WHERE r2_key = ?1 AND function_name = ?2 AND register_idx >= (1 << ?3)
+                   AND EXISTS (SELECT 1 FROM function_compactions
+                               WHERE r2_key = ?1 AND function_name = ?2 AND bits = ?3)",
+            )
+            .bind(&params)?,
+            read_state(db, &row.r2_key, &row.function_name)?,
+        ];
+        let results = db.batch(statements).await?;
+
+        let state_rows = |index: usize| -> Result<Vec<RegisterRow>> {
+            match results.get(index) {
+                Some(result) => result.results::<RegisterRow>(),
+                None => Ok(Vec::new()),
+            }
+        };
+        let before_rows = state_rows(0)?;
+        let after_rows = state_rows(4)?;
+        let folded_now = before_rows.first().is_some_and(|row| row.bits.is_none())
+            && after_rows.first().and_then(|row| row.bits) == Some(i64::from(bits));
+        if !folded_now {
+            // Improved since the idle scan, or compacted by a concurrent run
+            continue;
+        }
+
+        let rows_before = before_rows.len();
+        let rows_after = after_rows.len();
+        let before = hll_from_rows(before_rows)?;
+        let after = hll_from_rows(after_rows)?;
+        compacted.push(CompactedFunction {
+            rows_before,
+            rows_after,
+            estimate_before: before.count(),
+            estimate_after: after.count(),
+            r2_key: row.r2_key,
+            function_name: row.function_name,
+        });
+    }
+
+    Ok(compacted)
+}
+
 /// Get all HLL states for functions in a file
 pub async fn get_file_hll_states(
     db: &D1Database,
@@ -185,14 +825,263 @@ pub async fn get_file_hll_states(
 struct RegisterRow {
     register_idx: i64,
     min_hash: String,
+    seed: Option<String>,
+    /// Compaction width joined from `function_compactions`, if compacted
+    #[serde(default)]
+    bits: Option<i64>,
 }
 
 #[derive(serde::Deserialize)]
 struct MinHashRow {
+    register_idx: i64,
     min_hash: String,
 }
 
+#[derive(serde::Deserialize)]
+struct LowestHashRow {
+    lowest_hash: String,
+}
+
+#[derive(serde::Deserialize)]
+struct CompactionRow {
+    bits: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct IdleFunctionRow {
+    r2_key: String,
+    function_name: String,
+    last_updated: String,
+}
+
 #[derive(serde::Deserialize)]
 struct FunctionNameRow {
     function_name: String,
 }
+
+#[derive(serde::Deserialize)]
+struct FunctionRecordRow {
+    function_name: String,
+    lowest_hash: String,
+    lowest_seed: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splitmix(mut x: u64) -> u64 {
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    /// The rows D1 would return for `hll`, with the joined compaction width
+    fn rows_for(hll: &HyperLogLog, compacted_bits: Option<u8>) -> Vec<RegisterRow> {
+        hll.hashes()
+            .iter()
+            .enumerate()
+            .map(|(register, &hash)| RegisterRow {
+                register_idx: register as i64,
+                min_hash: format_hash(hash),
+                seed: (hash != u64::MAX).then(|| hll.seed(register).to_string()),
+                bits: compacted_bits.map(i64::from),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sparse_batch_only_flags_improving_registers() {
+        // Registers as D1 holds them; register 3 has no row yet
+        let mut registers = [Some(500u64), Some(40), Some(u64::MAX), None];
+        let before = registers;
+        let updates = [(0usize, 300u64), (1, 90), (3, 7), (0, 200), (0, 250)];
+
+        let mut flags = Vec::new();
+        for &(register, hash) in &updates {
+            let previous = registers[register];
+            let current = previous.map_or(hash, |previous| previous.min(hash));
+            registers[register] = Some(current);
+            flags.push(update_improved(previous, current, hash));
+        }
+
+        assert_eq!(flags, [true, false, true, true, false]);
+        assert_eq!(registers, [Some(200), Some(40), Some(u64::MAX), Some(7)]);
+        // Registers the batch didn't improve are left as they were
+        assert_eq!(registers[1], before[1]);
+        assert_eq!(registers[2], before[2]);
+    }
+
+    #[test]
+    fn test_record_tracks_deepest_hash_across_submissions() {
+        // The record upsert keeps the lower of the stored and submitted hash
+        let mut record: Option<u64> = None;
+        let mut flags = Vec::new();
+        for hash in [500u64, 300, 700, 100, 100, 250] {
+            let previous = record;
+            let current = previous.map_or(hash, |previous| previous.min(hash));
+            record = Some(current);
+            flags.push(update_improved(previous, current, hash));
+        }
+
+        assert_eq!(record, Some(100));
+        assert_eq!(flags, [true, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn test_stored_width_defaults_when_not_compacted() {
+        assert_eq!(stored_width(None), DEFAULT_HLL_BITS);
+        assert_eq!(stored_width(Some(3)), 3);
+        assert_eq!(stored_width(Some(0)), DEFAULT_HLL_BITS);
+        assert_eq!(
+            stored_width(Some(i64::from(DEFAULT_HLL_BITS) + 1)),
+            DEFAULT_HLL_BITS
+        );
+    }
+
+    #[test]
+    fn test_compacted_rows_read_back_at_reduced_width() {
+        let mut hll = HyperLogLog::new(DEFAULT_HLL_BITS);
+        for seed in 0..50_000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let full_rows = rows_for(&hll, None);
+        let stored = hll_from_rows(full_rows).unwrap();
+        assert_eq!(stored.bits(), DEFAULT_HLL_BITS);
+        assert!(stored.same_hashes(&hll));
+
+        let folded = stored.fold(COMPACTED_HLL_BITS).unwrap();
+        let compacted_rows = rows_for(&folded, Some(COMPACTED_HLL_BITS));
+        assert!(compacted_rows.len() < hll.hashes().len());
+
+        let restored = hll_from_rows(compacted_rows).unwrap();
+        assert_eq!(restored.bits(), COMPACTED_HLL_BITS);
+        assert!(restored.same_hashes(&folded));
+        assert_eq!(restored.verify_work(splitmix), Ok(()));
+
+        let error = (restored.count() - 50_000.0).abs() / 50_000.0;
+        assert!(
+            error < 2.0 * HyperLogLog::relative_error_for_bits(COMPACTED_HLL_BITS),
+            "estimate {} off by {:.1}%",
+            restored.count(),
+            error * 100.0
+        );
+    }
+
+    /// In-memory model of [`migrate_legacy_registers`] over one function's
+    /// `(register_idx, min_hash, seed)` rows: each step applies one of its
+    /// statements, in batch order
+    fn migrate_model(rows: &[(i64, &str, Option<&str>)]) -> Vec<(i64, String, Option<String>)> {
+        let num_registers = 1i64 << DEFAULT_HLL_BITS;
+        let mut rows: Vec<(i64, String, Option<String>)> = rows
+            .iter()
+            .map(|&(register, hash, seed)| (register, hash.to_string(), seed.map(String::from)))
+            .collect();
+
+        for (_, hash, seed) in rows.iter_mut() {
+            if hash.is_empty() || !hash.bytes().all(|b| b.is_ascii_digit()) || hash.len() > 20 {
+                *hash = U64_MAX_STR.to_string();
+                *seed = None;
+            }
+        }
+        for (_, hash, _) in rows.iter_mut() {
+            if hash.len() < 20 {
+                *hash = format!("{:0>20}", hash);
+            }
+        }
+        let wide: Vec<_> = rows
+            .iter()
+            .filter(|(register, _, _)| *register >= num_registers)
+            .cloned()
+            .collect();
+        for (register, hash, seed) in wide {
+            let target = register % num_registers;
+            match rows.iter_mut().find(|(register, _, _)| *register == target) {
+                Some(existing) if hash < existing.1 => *existing = (target, hash, seed),
+                Some(_) => {}
+                None => rows.push((target, hash, seed)),
+            }
+        }
+        rows.retain(|(register, _, _)| (0..num_registers).contains(register));
+        for (_, hash, seed) in rows.iter_mut() {
+            if seed.is_none() && hash.as_str() < U64_MAX_STR {
+                *seed = Some("0".to_string());
+            }
+        }
+
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_legacy_registers_are_normalized() {
+        let num_registers = 1i64 << DEFAULT_HLL_BITS;
+        let migrated = migrate_model(&[
+            // Already canonical
+            (0, "00000000000000000500", Some("11")),
+            // Non-numeric and overlong hashes
+            (1, "0xdeadbeef", Some("12")),
+            (2, "123456789012345678901", Some("13")),
+            // Short hash, compared as a number only once padded
+            (3, "42", Some("14")),
+            // Wide index beating the canonical row it folds into
+            (num_registers, "100", Some("15")),
+            // Wide index losing to the canonical row it folds into
+            (num_registers + 3, "00000000000000000099", Some("16")),
+            // Wide index with no canonical row yet
+            (2 * num_registers + 5, "00000000000000000777", Some("17")),
+            // Filled register without a seed
+            (6, "00000000000000000600", None),
+            // Negative indexes can't be folded
+            (-1, "00000000000000000001", Some("18")),
+        ]);
+
+        let expected = [
+            (0, "00000000000000000100", Some("15")),
+            (1, U64_MAX_STR, None),
+            (2, U64_MAX_STR, None),
+            (3, "00000000000000000042", Some("14")),
+            (5, "00000000000000000777", Some("17")),
+            (6, "00000000000000000600", Some("0")),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(register, hash, seed)| (register, hash.to_string(), seed.map(String::from)))
+            .collect();
+        assert_eq!(migrated, expected);
+
+        // Every migrated row reads back as a valid register
+        let hll = hll_from_rows(
+            migrated
+                .iter()
+                .map(|(register, hash, seed)| RegisterRow {
+                    register_idx: *register,
+                    min_hash: hash.clone(),
+                    seed: seed.clone(),
+                    bits: None,
+                })
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(hll.hashes()[0], 100);
+        assert_eq!(hll.seed(0), 15);
+        assert_eq!(hll.hashes()[6], 600);
+        assert_eq!(hll.seed(6), 0);
+    }
+
+    #[test]
+    fn test_legacy_register_migration_is_idempotent() {
+        let once = migrate_model(&[
+            (1, "abc", Some("1")),
+            (2, "7", None),
+            ((1i64 << DEFAULT_HLL_BITS) + 2, "3", Some("2")),
+        ]);
+        let rows: Vec<_> = once
+            .iter()
+            .map(|(register, hash, seed)| (*register, hash.as_str(), seed.as_deref()))
+            .collect();
+
+        assert_eq!(migrate_model(&rows), once);
+    }
+}
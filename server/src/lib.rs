@@ -8,9 +8,12 @@ use hyperloglog::DEFAULT_HLL_BITS;
 use js_sys::{Array, Function, Object, Reflect, Uint8Array};
 use serde::{Deserialize, Deserializer, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use worker::*;
+// `js_sys::Object` above shadows the glob, so R2 objects get their own name
+use worker::Object as R2Object;
 
 const OIDC_CONFIG_URL: &str =
     "https://token.actions.githubusercontent.com/.well-known/openid-configuration";
@@ -46,6 +49,15 @@ struct CiUploadResponse {
     function_count: usize,
     function_names: Vec<String>,
     r2_key: Option<String>,
+    aliases: Vec<FunctionAlias>,
+}
+
+/// A renamed export whose HLL history is carried over on upload
+#[derive(Serialize, Clone)]
+struct FunctionAlias {
+    from_r2_key: String,
+    from_function: String,
+    to_function: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -53,6 +65,9 @@ struct FunctionSummary {
     r2_key: String,
     name: String,
     estimated_tests: f64,
+    /// Deepest submitted proof, as decimal strings to avoid JS precision loss
+    lowest_hash: Option<String>,
+    lowest_seed: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -109,12 +124,40 @@ struct SubmitHashRequest {
     function_name: String,
     seed: String,
     hash: String,
+    /// Client's last-known server value for the target register. Registers
+    /// only ever decrease, so a hash at or above this value cannot improve.
+    #[serde(default)]
+    expected_current: Option<String>,
 }
 
 #[derive(Serialize)]
 struct SubmitHashResponse {
     ok: bool,
     improved: bool,
+    /// Omitted when the submission was rejected by the `expected_current` hint
+    /// without reading the stored state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_tests: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RegisterUpdatesRequest {
+    function_name: String,
+    updates: Vec<RegisterUpdateEntry>,
+}
+
+#[derive(Deserialize)]
+struct RegisterUpdateEntry {
+    register: usize,
+    seed: String,
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct RegisterUpdatesResponse {
+    ok: bool,
+    /// Per-update flags, in request order
+    improved: Vec<bool>,
     estimated_tests: f64,
 }
 
@@ -138,6 +181,54 @@ struct WasmFileHllStateResponse {
     functions: Vec<FunctionHllStateResponse>,
 }
 
+#[derive(Serialize)]
+struct VerifyFunctionResponse {
+    ok: bool,
+    r2_key: String,
+    function_name: String,
+    sample: f64,
+    checked: usize,
+    passed: usize,
+    failed: usize,
+    failed_registers: Vec<usize>,
+}
+
+/// Structured log line for a submission that lowered a register
+#[derive(Serialize)]
+struct SubmissionLog<'a> {
+    event: &'static str,
+    r2_key: &'a str,
+    function_name: &'a str,
+    register: usize,
+    old_hash: Option<String>,
+    new_hash: String,
+    duration_ms: f64,
+}
+
+#[derive(Serialize)]
+struct EnsureSchemaResponse {
+    ok: bool,
+    schema_version: u32,
+}
+
+#[derive(Serialize)]
+struct CompactedFunctionResponse {
+    r2_key: String,
+    function_name: String,
+    rows_before: usize,
+    rows_after: usize,
+    estimate_before: f64,
+    estimate_after: f64,
+}
+
+#[derive(Serialize)]
+struct CompactResponse {
+    ok: bool,
+    idle_days: u32,
+    bits: u8,
+    compacted: Vec<CompactedFunctionResponse>,
+}
+
 #[derive(Debug)]
 struct ApiError {
     status: u16,
@@ -250,6 +341,239 @@ fn to_worker_error(err: ApiError) -> Result<Response> {
     error_response(err.status, err.code, err.message)
 }
 
+/// Deepest hash accepted by `/api/test-results` when the
+/// `MAX_PLAUSIBLE_LEADING_ZEROS` variable is unset. 2^56 attempts is far
+/// beyond any honest run.
+const DEFAULT_MAX_PLAUSIBLE_LEADING_ZEROS: u32 = 56;
+
+/// Refuse a hash deeper than any honest search could plausibly reach
+///
+/// The server can't re-run the test function, so such proofs are rejected
+/// outright rather than trusted.
+fn check_plausible_depth(hash: u64, max_leading_zeros: u32) -> Result<(), ApiError> {
+    if hash.leading_zeros() > max_leading_zeros {
+        return Err(ApiError::new(
+            422,
+            "implausible_hash",
+            format!(
+                "Hash has {} leading zeros; at most {} are accepted",
+                hash.leading_zeros(),
+                max_leading_zeros
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn max_plausible_leading_zeros(env: &Env) -> u32 {
+    env.var("MAX_PLAUSIBLE_LEADING_ZEROS")
+        .ok()
+        .and_then(|value| value.to_string().parse().ok())
+        .unwrap_or(DEFAULT_MAX_PLAUSIBLE_LEADING_ZEROS)
+}
+
+/// Shallowest new record, in leading zeros, that triggers the
+/// `RECORD_WEBHOOK_URL` notification when `RECORD_WEBHOOK_MIN_DEPTH` is unset.
+const DEFAULT_RECORD_WEBHOOK_MIN_DEPTH: u32 = 32;
+
+/// How long a record notification may take before it is aborted
+const RECORD_WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct RecordWebhookPayload {
+    event: &'static str,
+    repository: String,
+    r2_key: String,
+    function_name: String,
+    seed: String,
+    hash: String,
+    depth: u32,
+}
+
+/// Webhook notified of new record proofs, from the `RECORD_WEBHOOK_URL`
+/// secret or variable. Unset or empty disables notifications.
+fn record_webhook_url(env: &Env) -> Option<String> {
+    env.secret("RECORD_WEBHOOK_URL")
+        .or_else(|_| env.var("RECORD_WEBHOOK_URL"))
+        .ok()
+        .map(|value| value.to_string())
+        .filter(|url| !url.is_empty())
+}
+
+fn record_webhook_min_depth(env: &Env) -> u32 {
+    env.var("RECORD_WEBHOOK_MIN_DEPTH")
+        .ok()
+        .and_then(|value| value.to_string().parse().ok())
+        .unwrap_or(DEFAULT_RECORD_WEBHOOK_MIN_DEPTH)
+}
+
+/// Repository slug encoded in the first segment of an R2 key by `ci-upload`
+fn repository_from_r2_key(r2_key: &str) -> String {
+    r2_key
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .replace("__", "/")
+}
+
+/// Webhook payload for a submitted proof, if it is a function's new deepest
+/// record and at least `min_depth` leading zeros deep
+fn record_webhook_payload(
+    new_record: bool,
+    min_depth: u32,
+    r2_key: &str,
+    function_name: &str,
+    seed: u64,
+    hash: u64,
+) -> Option<RecordWebhookPayload> {
+    let depth = hash.leading_zeros();
+    if !new_record || depth < min_depth {
+        return None;
+    }
+
+    Some(RecordWebhookPayload {
+        event: "new_record",
+        repository: repository_from_r2_key(r2_key),
+        r2_key: r2_key.to_string(),
+        function_name: function_name.to_string(),
+        seed: seed.to_string(),
+        hash: hash.to_string(),
+        depth,
+    })
+}
+
+/// Queue a notification if `hash` is a function's new deepest proof.
+///
+/// Only records at least `RECORD_WEBHOOK_MIN_DEPTH` deep are sent. The
+/// request runs under `wait_until`, so the submission response never waits
+/// on it, and it is aborted after [`RECORD_WEBHOOK_TIMEOUT`].
+fn notify_new_record(
+    env: &Env,
+    worker_ctx: &Context,
+    new_record: bool,
+    r2_key: &str,
+    function_name: &str,
+    seed: u64,
+    hash: u64,
+) {
+    let Some(url) = record_webhook_url(env) else {
+        return;
+    };
+    let Some(payload) = record_webhook_payload(
+        new_record,
+        record_webhook_min_depth(env),
+        r2_key,
+        function_name,
+        seed,
+        hash,
+    ) else {
+        return;
+    };
+
+    worker_ctx.wait_until(async move {
+        let controller = AbortController::default();
+        let signal = controller.signal();
+        let sent = with_timeout(
+            send_record_webhook(&url, &payload, &signal),
+            RECORD_WEBHOOK_TIMEOUT,
+        )
+        .await;
+
+        let err = match sent {
+            Some(Ok(())) => return,
+            Some(Err(err)) => err.to_string(),
+            None => {
+                // Dropping the fetch future doesn't cancel the request itself
+                controller.abort();
+                "timed out".to_string()
+            }
+        };
+        console_log!(
+            "[WARN] Record webhook for {}/{} failed: {}",
+            payload.r2_key,
+            payload.function_name,
+            err
+        );
+    });
+}
+
+/// Run `future` until it completes or `timeout` elapses, whichever is first.
+///
+/// Returns `None` on timeout. The pending side is dropped either way, so a
+/// request that finishes early doesn't keep its timer (and the invocation)
+/// alive.
+async fn with_timeout<F: std::future::Future>(
+    future: F,
+    timeout: std::time::Duration,
+) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let mut delay = std::pin::pin!(Delay::from(timeout));
+    std::future::poll_fn(|cx| {
+        if let std::task::Poll::Ready(output) = std::future::Future::poll(future.as_mut(), cx) {
+            return std::task::Poll::Ready(Some(output));
+        }
+        std::future::Future::poll(delay.as_mut(), cx).map(|()| None)
+    })
+    .await
+}
+
+async fn send_record_webhook(
+    url: &str,
+    payload: &RecordWebhookPayload,
+    signal: &AbortSignal,
+) -> Result<()> {
+    let body = serde_json::to_string(payload)
+        .map_err(|e| Error::RustError(format!("Failed to serialize webhook payload: {}", e)))?;
+
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/json")?;
+    let mut init = RequestInit::new();
+    init.with_method(Method::Post)
+        .with_headers(headers)
+        .with_body(Some(body.into()));
+
+    let request = Request::new_with_init(url, &init)?;
+    let response = Fetch::Request(request).send_with_signal(signal).await?;
+    if !(200..300).contains(&response.status_code()) {
+        return Err(Error::RustError(format!(
+            "webhook returned status {}",
+            response.status_code()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Look up the D1 database binding.
+///
+/// A missing binding is a common local-dev setup mistake, so it is reported
+/// as a clear 503 rather than an opaque internal error.
+fn hll_db(env: &Env) -> std::result::Result<worker::d1::D1Database, ApiError> {
+    env.d1("HLL_DB").map_err(|_| database_not_configured())
+}
+
+fn database_not_configured() -> ApiError {
+    ApiError::new(
+        503,
+        "database_not_configured",
+        "Database not configured: missing D1 binding HLL_DB",
+    )
+}
+
+/// Look up the KV catalog binding, reporting a missing binding as a 503.
+fn catalog_kv(env: &Env) -> std::result::Result<worker::kv::KvStore, ApiError> {
+    env.kv("CATALOG").map_err(|_| catalog_not_configured())
+}
+
+fn catalog_not_configured() -> ApiError {
+    ApiError::new(
+        503,
+        "catalog_not_configured",
+        "Catalog not configured: missing KV binding CATALOG",
+    )
+}
+
 fn decode_base64url(input: &str) -> std::result::Result<Vec<u8>, ApiError> {
     URL_SAFE_NO_PAD.decode(input).map_err(|_| {
         ApiError::new(
@@ -391,6 +715,25 @@ fn extract_bearer_token(req: &Request) -> std::result::Result<String, ApiError>
     Ok(token.to_string())
 }
 
+/// Check the request carries the operator token from the `ADMIN_TOKEN` secret.
+fn require_admin(req: &Request, env: &Env) -> std::result::Result<(), ApiError> {
+    let expected = env.secret("ADMIN_TOKEN").map_err(|_| {
+        ApiError::new(
+            503,
+            "admin_not_configured",
+            "Admin endpoints disabled: missing ADMIN_TOKEN secret",
+        )
+    })?;
+    let token = extract_bearer_token(req)?;
+
+    // Compare digests so the check doesn't leak the token length or prefix
+    if Sha256::digest(token.as_bytes()) != Sha256::digest(expected.to_string().as_bytes()) {
+        return Err(ApiError::new(403, "forbidden", "Invalid admin token"));
+    }
+
+    Ok(())
+}
+
 async fn fetch_json<T: serde::de::DeserializeOwned>(
     url: &str,
     headers: &[(&str, &str)],
@@ -706,6 +1049,92 @@ struct R2WasmMetadata {
     functions: Vec<String>,
 }
 
+/// Parse the optional `aliases` form field, a JSON object of `old_name -> new_name`
+fn parse_function_aliases(
+    value: Option<String>,
+) -> std::result::Result<BTreeMap<String, String>, ApiError> {
+    let value = match value.map(|value| value.trim().to_string()) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    serde_json::from_str(&value).map_err(|_| {
+        ApiError::new(
+            400,
+            "invalid_aliases",
+            "aliases must be a JSON object mapping old function names to new ones",
+        )
+    })
+}
+
+/// Match each alias to the file in the previous latest version that exported it
+///
+/// Only `u64 -> u64` exports are tracked, so an alias keeps its signature
+/// exactly when the old name was a tracked export of the previous version and
+/// the new name is one of `function_names`. The old name must be gone from the
+/// new module, and no two aliases may target the same function.
+fn resolve_function_aliases(
+    aliases: &BTreeMap<String, String>,
+    previous: Option<&VersionMetadata>,
+    function_names: &[String],
+) -> std::result::Result<Vec<FunctionAlias>, ApiError> {
+    let mut resolved: Vec<FunctionAlias> = Vec::with_capacity(aliases.len());
+
+    for (from, to) in aliases {
+        if !function_names.contains(to) {
+            return Err(ApiError::new(
+                400,
+                "invalid_alias",
+                format!(
+                    "Alias target `{}` is not a u64 -> u64 export of the uploaded module",
+                    to
+                ),
+            ));
+        }
+        if function_names.contains(from) {
+            return Err(ApiError::new(
+                400,
+                "invalid_alias",
+                format!(
+                    "`{}` is still exported; aliases are only for renamed functions",
+                    from
+                ),
+            ));
+        }
+        if resolved.iter().any(|alias| &alias.to_function == to) {
+            return Err(ApiError::new(
+                400,
+                "invalid_alias",
+                format!("More than one alias targets `{}`", to),
+            ));
+        }
+
+        // The most recently uploaded file wins if several exported the name
+        let source = previous
+            .into_iter()
+            .flat_map(|version| version.files.iter().rev())
+            .find(|file| file.functions.contains(from))
+            .ok_or_else(|| {
+                ApiError::new(
+                    400,
+                    "invalid_alias",
+                    format!(
+                        "Alias source `{}` is not a u64 -> u64 export of the latest version",
+                        from
+                    ),
+                )
+            })?;
+
+        resolved.push(FunctionAlias {
+            from_r2_key: source.r2_key.clone(),
+            from_function: from.clone(),
+            to_function: to.clone(),
+        });
+    }
+
+    Ok(resolved)
+}
+
 fn parse_u64_string(value: &str, field_name: &'static str) -> std::result::Result<u64, ApiError> {
     value.parse::<u64>().map_err(|_| {
         ApiError::new(
@@ -716,16 +1145,140 @@ fn parse_u64_string(value: &str, field_name: &'static str) -> std::result::Resul
     })
 }
 
+fn function_summary(
+    r2_key: &str,
+    name: &str,
+    estimated_tests: f64,
+    records: &[hll_store::FunctionRecord],
+) -> FunctionSummary {
+    let record = records.iter().find(|record| record.function_name == name);
+    FunctionSummary {
+        r2_key: r2_key.to_string(),
+        name: name.to_string(),
+        estimated_tests,
+        lowest_hash: record.map(|record| record.lowest_hash.to_string()),
+        lowest_seed: record.map(|record| record.lowest_seed.to_string()),
+    }
+}
+
+/// Default bound, in seconds, on how stale a snapshot served by the read
+/// path may be when `READ_STALENESS_SECS` is unset
+const DEFAULT_READ_STALENESS_SECS: u64 = 10;
+
+fn read_staleness_secs(env: &Env) -> u64 {
+    env.var("READ_STALENESS_SECS")
+        .ok()
+        .and_then(|value| value.to_string().parse().ok())
+        .unwrap_or(DEFAULT_READ_STALENESS_SECS)
+}
+
+/// Age in seconds of a snapshot computed at `computed_at`, if it is still
+/// fresh enough to serve under a `staleness` bound. A bound of 0 serves no
+/// snapshot at all.
+fn fresh_snapshot_age(computed_at: u64, now: u64, staleness: u64) -> Option<u64> {
+    let age = now.saturating_sub(computed_at);
+    (staleness > 0 && age < staleness).then_some(age)
+}
+
+/// Eventually consistent read path for polled aggregate endpoints.
+///
+/// Serves the KV snapshot of aggregate `name` while it is younger than the
+/// `READ_STALENESS_SECS` bound, and only otherwise runs `compute` against D1
+/// and stores the result as the new snapshot. Writes such as
+/// `/api/test-results` never go through here and always hit D1 directly, so
+/// frequent polling doesn't contend with submissions. A bound of 0 disables
+/// snapshots. `compute` returning `None` (e.g. an unknown repository) is not
+/// cached.
+///
+/// Returns the body and its age in seconds.
+async fn snapshot_read<F, Fut>(
+    env: &Env,
+    kv: &worker::kv::KvStore,
+    name: &str,
+    compute: F,
+) -> Result<Option<(String, u64)>>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<Option<String>>>,
+{
+    let staleness = read_staleness_secs(env);
+    let now = now_unix_secs();
+
+    if staleness > 0 {
+        match catalog::get_cached_aggregate(kv, name).await {
+            Ok(Some(cached)) => {
+                if let Some(age) = fresh_snapshot_age(cached.computed_at, now, staleness) {
+                    return Ok(Some((cached.body, age)));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => console_log!("[WARN] Failed to read {} snapshot: {:?}", name, e),
+        }
+    }
+
+    let Some(body) = compute().await? else {
+        return Ok(None);
+    };
+
+    if staleness > 0 {
+        let cached = catalog::CachedAggregate {
+            computed_at: now,
+            body,
+        };
+        if let Err(e) = catalog::put_cached_aggregate(kv, name, &cached, staleness).await {
+            console_log!("[WARN] Failed to write {} snapshot: {:?}", name, e);
+        }
+        return Ok(Some((cached.body, 0)));
+    }
+
+    Ok(Some((body, 0)))
+}
+
 async fn handle_list_repositories(env: Env) -> Result<Response> {
-    let kv = env.kv("CATALOG")?;
-    let db = env.d1("HLL_DB")?;
+    let kv = match catalog_kv(&env) {
+        Ok(kv) => kv,
+        Err(err) => return to_worker_error(err),
+    };
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+
+    let (kv_ref, db_ref) = (&kv, &db);
+    let snapshot = snapshot_read(&env, &kv, "repositories", move || async move {
+        let listing = list_repositories(kv_ref, db_ref).await?;
+        Ok(Some(serde_json::to_string(&listing)?))
+    })
+    .await?;
+
+    match snapshot {
+        Some((body, age)) => cached_json_response(body, age),
+        None => error_response(500, "internal_error", "Repository listing unavailable"),
+    }
+}
+
+/// JSON response for a cached aggregate, with its age in `X-Cache-Age` seconds
+fn cached_json_response(body: String, age_secs: u64) -> Result<Response> {
+    ResponseBuilder::new()
+        .with_header("Content-Type", "application/json")?
+        .with_header("X-Cache-Age", &age_secs.to_string())?
+        .from_bytes(body.into_bytes())
+}
 
+/// Aggregate estimates across every repository in the catalog.
+///
+/// Scans every function's registers, so callers should go through
+/// [`snapshot_read`].
+async fn list_repositories(
+    kv: &worker::kv::KvStore,
+    db: &worker::d1::D1Database,
+) -> Result<RepositoryListResponse> {
     // Ensure schema exists
-    if let Err(e) = hll_store::ensure_schema(&db).await {
+    if let Err(e) = hll_store::ensure_schema(db).await {
         console_log!("[WARN] Failed to ensure D1 schema: {:?}", e);
     }
 
-    let repo_names = catalog::list_repos(&kv).await?;
+    let repo_names = catalog::list_repos(kv).await?;
 
     let mut repositories = Vec::new();
     let mut total_estimated_tests = 0.0;
@@ -734,14 +1287,14 @@ async fn handle_list_repositories(env: Env) -> Result<Response> {
     let mut function_count = 0usize;
 
     for repo_name in &repo_names {
-        if let Some(repo_meta) = catalog::get_repo(&kv, repo_name).await? {
+        if let Some(repo_meta) = catalog::get_repo(kv, repo_name).await? {
             let mut repo_estimated_tests = 0.0;
             let mut repo_file_count = 0usize;
             let mut repo_function_count = 0usize;
             let mut latest_estimated_tests = 0.0;
 
             for version in &repo_meta.versions {
-                if let Some(version_meta) = catalog::get_version(&kv, repo_name, version).await? {
+                if let Some(version_meta) = catalog::get_version(kv, repo_name, version).await? {
                     version_count += 1;
 
                     for file in &version_meta.files {
@@ -749,7 +1302,7 @@ async fn handle_list_repositories(env: Env) -> Result<Response> {
                         file_count += 1;
 
                         // Get HLL states for this file
-                        let states = hll_store::get_file_hll_states(&db, &file.r2_key).await?;
+                        let states = hll_store::get_file_hll_states(db, &file.r2_key).await?;
                         for (_, hll) in &states {
                             repo_function_count += 1;
                             function_count += 1;
@@ -785,26 +1338,53 @@ async fn handle_list_repositories(env: Env) -> Result<Response> {
             .unwrap_or(std::cmp::Ordering::Equal)
     });
 
-    json_response(
-        200,
-        &RepositoryListResponse {
-            total_estimated_tests,
-            repository_count: repositories.len(),
-            version_count,
-            file_count,
-            function_count,
-            repositories,
-        },
-    )
+    Ok(RepositoryListResponse {
+        total_estimated_tests,
+        repository_count: repositories.len(),
+        version_count,
+        file_count,
+        function_count,
+        repositories,
+    })
 }
 
 async fn handle_repository_detail(env: Env, repository: String) -> Result<Response> {
-    let kv = env.kv("CATALOG")?;
-    let db = env.d1("HLL_DB")?;
+    let kv = match catalog_kv(&env) {
+        Ok(kv) => kv,
+        Err(err) => return to_worker_error(err),
+    };
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema_once(&db).await?;
+
+    let name = format!("repository:{}", repository);
+    let (kv_ref, db_ref, repository_ref) = (&kv, &db, &repository);
+    let snapshot = snapshot_read(&env, &kv, &name, move || async move {
+        let detail = repository_detail(kv_ref, db_ref, repository_ref).await?;
+        detail
+            .map(|detail| serde_json::to_string(&detail).map_err(Error::from))
+            .transpose()
+    })
+    .await?;
 
-    let repo_meta = match catalog::get_repo(&kv, &repository).await? {
+    match snapshot {
+        Some((body, age)) => cached_json_response(body, age),
+        None => error_response(404, "not_found", "Repository not found"),
+    }
+}
+
+/// Estimates for every version, file and function of one repository, or
+/// `None` if it isn't in the catalog.
+async fn repository_detail(
+    kv: &worker::kv::KvStore,
+    db: &worker::d1::D1Database,
+    repository: &str,
+) -> Result<Option<RepositoryDetailResponse>> {
+    let repo_meta = match catalog::get_repo(kv, repository).await? {
         Some(meta) => meta,
-        None => return error_response(404, "not_found", "Repository not found"),
+        None => return Ok(None),
     };
 
     let mut versions = Vec::new();
@@ -812,12 +1392,13 @@ async fn handle_repository_detail(env: Env, repository: String) -> Result<Respon
     let mut latest_estimated_tests = 0.0;
 
     for version_name in &repo_meta.versions {
-        if let Some(version_meta) = catalog::get_version(&kv, &repository, version_name).await? {
+        if let Some(version_meta) = catalog::get_version(kv, repository, version_name).await? {
             let mut version_estimated_tests = 0.0;
             let mut files = Vec::new();
 
             for file in &version_meta.files {
-                let states = hll_store::get_file_hll_states(&db, &file.r2_key).await?;
+                let states = hll_store::get_file_hll_states(db, &file.r2_key).await?;
+                let records = hll_store::get_file_records(db, &file.r2_key).await?;
 
                 let functions: Vec<FunctionSummary> = states
                     .iter()
@@ -825,11 +1406,7 @@ async fn handle_repository_detail(env: Env, repository: String) -> Result<Respon
                         let estimate = hll.count();
                         version_estimated_tests += estimate;
 
-                        FunctionSummary {
-                            r2_key: file.r2_key.clone(),
-                            name: name.clone(),
-                            estimated_tests: estimate,
-                        }
+                        function_summary(&file.r2_key, name, estimate, &records)
                     })
                     .collect();
 
@@ -862,21 +1439,25 @@ async fn handle_repository_detail(env: Env, repository: String) -> Result<Respon
     // Sort versions descending
     versions.sort_by(|a, b| b.version.cmp(&a.version));
 
-    json_response(
-        200,
-        &RepositoryDetailResponse {
-            repository,
-            latest_version: repo_meta.latest_version,
-            total_estimated_tests,
-            latest_estimated_tests,
-            versions,
-        },
-    )
+    Ok(Some(RepositoryDetailResponse {
+        repository: repository.to_string(),
+        latest_version: repo_meta.latest_version,
+        total_estimated_tests,
+        latest_estimated_tests,
+        versions,
+    }))
 }
 
 async fn handle_latest_catalog(env: Env, repository: String) -> Result<Response> {
-    let kv = env.kv("CATALOG")?;
-    let db = env.d1("HLL_DB")?;
+    let kv = match catalog_kv(&env) {
+        Ok(kv) => kv,
+        Err(err) => return to_worker_error(err),
+    };
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema_once(&db).await?;
 
     let repo_meta = match catalog::get_repo(&kv, &repository).await? {
         Some(meta) => meta,
@@ -896,14 +1477,11 @@ async fn handle_latest_catalog(env: Env, repository: String) -> Result<Response>
     let mut files = Vec::new();
     for file in &version_meta.files {
         let states = hll_store::get_file_hll_states(&db, &file.r2_key).await?;
+        let records = hll_store::get_file_records(&db, &file.r2_key).await?;
 
         let functions: Vec<FunctionSummary> = states
             .iter()
-            .map(|(name, hll)| FunctionSummary {
-                r2_key: file.r2_key.clone(),
-                name: name.clone(),
-                estimated_tests: hll.count(),
-            })
+            .map(|(name, hll)| function_summary(&file.r2_key, name, hll.count(), &records))
             .collect();
 
         files.push(WasmFileSummary {
@@ -924,19 +1502,22 @@ async fn handle_latest_catalog(env: Env, repository: String) -> Result<Response>
     )
 }
 
-async fn handle_get_wasm_file(env: Env, r2_key: String) -> Result<Response> {
-    let bucket = env
-        .bucket("WASM_BUCKET")
-        .map_err(|_| Error::RustError("Missing R2 bucket binding WASM_BUCKET".to_string()))?;
+fn wasm_bucket(env: &Env) -> Result<Bucket> {
+    env.bucket("WASM_BUCKET")
+        .map_err(|_| Error::RustError("Missing R2 bucket binding WASM_BUCKET".to_string()))
+}
+
+async fn load_wasm_bytes(env: &Env, r2_key: &str) -> Result<Option<Vec<u8>>> {
+    let bucket = wasm_bucket(env)?;
 
     let object = bucket
-        .get(&r2_key)
+        .get(r2_key)
         .execute()
         .await
         .map_err(|e| Error::RustError(format!("Failed reading object from R2: {}", e)))?;
 
     let Some(object) = object else {
-        return error_response(404, "not_found", "WASM file not found");
+        return Ok(None);
     };
 
     let body = object
@@ -946,67 +1527,675 @@ async fn handle_get_wasm_file(env: Env, r2_key: String) -> Result<Response> {
         .bytes()
         .await
         .map_err(|e| Error::RustError(format!("Failed reading R2 object body: {}", e)))?;
-    ResponseBuilder::new()
-        .with_header("Content-Type", "application/wasm")?
-        .from_bytes(bytes)
+    Ok(Some(bytes))
 }
 
-async fn handle_get_wasm_file_hll_state(env: Env, r2_key: String) -> Result<Response> {
-    let db = env.d1("HLL_DB")?;
+/// Format milliseconds since the epoch as an HTTP date
+fn http_date(millis: u64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(millis as f64))
+        .to_utc_string()
+        .into()
+}
 
-    let states = hll_store::get_file_hll_states(&db, &r2_key).await?;
+/// Whether the request's validators show the client already has this object
+fn wasm_not_modified(req: &Request, object: &R2Object) -> Result<bool> {
+    let if_none_match = req.headers().get("If-None-Match")?;
+    let modified_since = req
+        .headers()
+        .get("If-Modified-Since")?
+        .map(|value| js_sys::Date::parse(&value));
+
+    Ok(conditional_get_matches(
+        if_none_match.as_deref(),
+        modified_since,
+        &object.http_etag(),
+        object.uploaded().as_millis(),
+    ))
+}
 
-    if states.is_empty() {
-        return error_response(404, "not_found", "WASM file has no registered functions");
+/// Conditional GET check for an object with `etag` uploaded at `uploaded_ms`
+///
+/// `if_modified_since_ms` is the parsed `If-Modified-Since` date, NaN when it
+/// didn't parse. `If-None-Match` takes precedence over `If-Modified-Since`,
+/// as in RFC 9110.
+fn conditional_get_matches(
+    if_none_match: Option<&str>,
+    if_modified_since_ms: Option<f64>,
+    etag: &str,
+    uploaded_ms: u64,
+) -> bool {
+    if let Some(if_none_match) = if_none_match {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
     }
 
-    let functions: Vec<FunctionHllStateResponse> = states
-        .into_iter()
-        .map(|(name, hll)| FunctionHllStateResponse {
-            function_name: name,
-            hll_bits: DEFAULT_HLL_BITS,
-            hashes: hll.hashes().iter().map(|v| v.to_string()).collect(),
-        })
-        .collect();
+    match if_modified_since_ms {
+        // HTTP dates have whole-second precision
+        Some(since) if since.is_finite() && since >= 0.0 => {
+            uploaded_ms / 1000 <= since as u64 / 1000
+        }
+        _ => false,
+    }
+}
 
-    json_response(200, &WasmFileHllStateResponse { r2_key, functions })
+/// Start a wasm download response with the object's validators
+///
+/// Keys embed the module's SHA-256, so an object never changes once stored.
+fn wasm_response_builder(object: &R2Object) -> Result<ResponseBuilder> {
+    ResponseBuilder::new()
+        .with_header("Content-Type", "application/wasm")?
+        .with_header("ETag", &object.http_etag())?
+        .with_header("Last-Modified", &http_date(object.uploaded().as_millis()))?
+        .with_header("Cache-Control", "public, max-age=31536000, immutable")
 }
 
-async fn handle_submit_test_result(mut req: Request, env: Env) -> Result<Response> {
-    let body: SubmitHashRequest = req
-        .json()
+async fn handle_get_wasm_file(req: Request, env: Env, r2_key: String) -> Result<Response> {
+    let object = wasm_bucket(&env)?
+        .get(&r2_key)
+        .execute()
         .await
-        .map_err(|e| Error::RustError(format!("Invalid JSON body: {}", e)))?;
-
-    let seed = match parse_u64_string(&body.seed, "seed") {
-        Ok(value) => value,
-        Err(err) => return to_worker_error(err),
-    };
-    let hash = match parse_u64_string(&body.hash, "hash") {
-        Ok(value) => value,
-        Err(err) => return to_worker_error(err),
+        .map_err(|e| Error::RustError(format!("Failed reading object from R2: {}", e)))?;
+    let Some(object) = object else {
+        return error_response(404, "not_found", "WASM file not found");
     };
 
-    let db = env.d1("HLL_DB")?;
-
-    // Ensure schema exists
-    if let Err(e) = hll_store::ensure_schema(&db).await {
-        console_log!("[WARN] Failed to ensure D1 schema: {:?}", e);
+    if wasm_not_modified(&req, &object)? {
+        return Ok(wasm_response_builder(&object)?.with_status(304).empty());
     }
 
-    // Submit the hash atomically
-    let improved =
-        hll_store::submit_hash(&db, &body.r2_key, &body.function_name, seed, hash).await?;
-
-    // Get updated HLL state for the estimate
+    let body = object
+        .body()
+        .ok_or_else(|| Error::RustError("R2 object had no body".to_string()))?;
+    let bytes = body
+        .bytes()
+        .await
+        .map_err(|e| Error::RustError(format!("Failed reading R2 object body: {}", e)))?;
+
+    wasm_response_builder(&object)?.from_bytes(bytes)
+}
+
+/// Report a wasm file's size and validators without its body
+async fn handle_head_wasm_file(env: Env, r2_key: String) -> Result<Response> {
+    let object = wasm_bucket(&env)?
+        .head(&r2_key)
+        .await
+        .map_err(|e| Error::RustError(format!("Failed reading object from R2: {}", e)))?;
+    let Some(object) = object else {
+        return Ok(ResponseBuilder::new().with_status(404).empty());
+    };
+
+    Ok(wasm_response_builder(&object)?
+        .with_header("Content-Length", &object.size().to_string())?
+        .empty())
+}
+
+/// Fuel each call may burn while `/api/wasm-verify` re-runs a seed. Hash
+/// functions take a few thousand instructions, so only a module that loops
+/// or does unbounded work per seed runs out.
+const VERIFY_FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Parse the `?sample=` fraction of filled registers to verify
+fn parse_verify_sample(value: &str) -> std::result::Result<f64, ApiError> {
+    match value.parse::<f64>() {
+        Ok(parsed) if parsed > 0.0 && parsed <= 1.0 => Ok(parsed),
+        _ => Err(ApiError::new(
+            400,
+            "invalid_sample",
+            "sample must be a fraction in (0, 1]",
+        )),
+    }
+}
+
+/// Check a sample of `hll`'s registers against a module call.
+///
+/// `call` returns `None` when the call trapped or ran out of fuel. That can't
+/// reproduce any stored hash, and stored hashes are never `u64::MAX`, so
+/// those registers count as failures.
+fn verify_sketch(
+    hll: &hyperloglog::HyperLogLog,
+    sample: f64,
+    call: impl Fn(u64) -> Option<u64>,
+) -> hyperloglog::VerifyReport {
+    hll.probabilistic_verify(sample, |seed| call(seed).unwrap_or(u64::MAX))
+}
+
+/// Re-run stored `(seed, hash)` pairs of one function through its module.
+///
+/// Takes `?function=` (required) and `?sample=` (fraction of filled
+/// registers to check, default 1.0). Each function can be verified at most
+/// once per cooldown since every check executes the module.
+async fn handle_verify_function(req: Request, env: Env, r2_key: String) -> Result<Response> {
+    let url = req.url()?;
+    let mut function_name = None;
+    let mut sample = 1.0;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "function" => function_name = Some(value.into_owned()),
+            "sample" => match parse_verify_sample(&value) {
+                Ok(parsed) => sample = parsed,
+                Err(err) => return to_worker_error(err),
+            },
+            _ => {}
+        }
+    }
+    let Some(function_name) = function_name else {
+        return error_response(400, "missing_function", "Missing function query parameter");
+    };
+
+    let kv = match catalog_kv(&env) {
+        Ok(kv) => kv,
+        Err(err) => return to_worker_error(err),
+    };
+    if catalog::check_and_mark_verify(&kv, &r2_key, &function_name).await? {
+        return error_response(
+            429,
+            "rate_limited",
+            "This function was verified recently; try again later",
+        );
+    }
+
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema_once(&db).await?;
+    let hll = hll_store::get_hll_state(&db, &r2_key, &function_name).await?;
+
+    let Some(wasm_bytes) = load_wasm_bytes(&env, &r2_key).await? else {
+        return error_response(404, "not_found", "WASM file not found");
+    };
+
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    let engine = wasmi::Engine::new(&config);
+    let module = match wasmi::Module::new(&engine, &wasm_bytes[..]) {
+        Ok(module) => module,
+        Err(e) => return error_response(422, "invalid_wasm", format!("WASM parse failed: {}", e)),
+    };
+    let mut store = wasmi::Store::new(&engine, ());
+    store
+        .set_fuel(VERIFY_FUEL_PER_CALL)
+        .map_err(|e| Error::RustError(format!("Failed setting fuel: {}", e)))?;
+    let instance = match wasmi::Linker::new(&engine).instantiate_and_start(&mut store, &module) {
+        Ok(instance) => instance,
+        Err(e) => {
+            return error_response(
+                422,
+                "invalid_wasm",
+                format!("WASM instantiation failed: {}", e),
+            )
+        }
+    };
+    let func = match instance.get_typed_func::<u64, u64>(&store, &function_name) {
+        Ok(func) => func,
+        Err(_) => {
+            return error_response(404, "not_found", "Function is not exported as u64 -> u64")
+        }
+    };
+
+    // Every call gets a fresh budget, so one slow seed can't starve the rest
+    let store = std::cell::RefCell::new(store);
+    let report = verify_sketch(&hll, sample, |seed| {
+        let mut store = store.borrow_mut();
+        store.set_fuel(VERIFY_FUEL_PER_CALL).ok()?;
+        func.call(&mut *store, seed).ok()
+    });
+
+    json_response(
+        200,
+        &VerifyFunctionResponse {
+            ok: true,
+            r2_key,
+            function_name,
+            sample,
+            checked: report.checked,
+            passed: report.passed,
+            failed: report.failed_registers.len(),
+            failed_registers: report.failed_registers,
+        },
+    )
+}
+
+async fn handle_get_wasm_file_hll_state(env: Env, r2_key: String) -> Result<Response> {
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema_once(&db).await?;
+
+    let states = hll_store::get_file_hll_states(&db, &r2_key).await?;
+
+    if states.is_empty() {
+        return error_response(404, "not_found", "WASM file has no registered functions");
+    }
+
+    let functions: Vec<FunctionHllStateResponse> = states
+        .into_iter()
+        .map(|(name, hll)| FunctionHllStateResponse {
+            function_name: name,
+            hll_bits: hll.bits(),
+            hashes: hll.hashes().iter().map(|v| v.to_string()).collect(),
+        })
+        .collect();
+
+    json_response(200, &WasmFileHllStateResponse { r2_key, functions })
+}
+
+/// Most entries accepted in one sparse register batch. Each entry becomes
+/// three D1 statements, and an honest batch never needs more than one per
+/// register.
+const MAX_REGISTER_UPDATES: usize = 1 << DEFAULT_HLL_BITS;
+
+/// Parse and check a sparse register batch against the function's stored width
+///
+/// Rejects oversized batches, registers outside `2^bits`, hashes that don't
+/// belong to their register at that width, and hashes deeper than
+/// `max_leading_zeros`.
+fn validate_register_updates(
+    entries: &[RegisterUpdateEntry],
+    bits: u8,
+    max_leading_zeros: u32,
+) -> std::result::Result<Vec<hll_store::RegisterUpdate>, ApiError> {
+    if entries.len() > MAX_REGISTER_UPDATES {
+        return Err(ApiError::new(
+            400,
+            "too_many_updates",
+            format!(
+                "{} updates in one batch; at most {} are accepted",
+                entries.len(),
+                MAX_REGISTER_UPDATES
+            ),
+        ));
+    }
+
+    let num_registers = 1usize << bits;
+    let mut updates = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let seed = parse_u64_string(&entry.seed, "seed")?;
+        let hash = parse_u64_string(&entry.hash, "hash")?;
+
+        if entry.register >= num_registers {
+            return Err(ApiError::new(
+                400,
+                "invalid_register",
+                format!(
+                    "register {} is out of range for {} registers",
+                    entry.register, num_registers
+                ),
+            ));
+        }
+        if (hash as usize) & (num_registers - 1) != entry.register {
+            return Err(ApiError::new(
+                400,
+                "register_mismatch",
+                format!(
+                    "hash {} does not belong to register {}",
+                    hash, entry.register
+                ),
+            ));
+        }
+        check_plausible_depth(hash, max_leading_zeros)?;
+
+        updates.push(hll_store::RegisterUpdate {
+            register_idx: entry.register,
+            seed,
+            hash,
+        });
+    }
+
+    Ok(updates)
+}
+
+/// Lowest [`hyperloglog::HyperLogLog::plausibility_score`] a submission may
+/// push a function's sketch down to.
+///
+/// Calibrated at `DEFAULT_HLL_BITS`: over 20,000 honest runs at each size,
+/// 32-register sketches never scored below 0.54, the low point being around
+/// 80 attempts while occupancy is still partial. Filling every register with
+/// depth-0 hashes scores 0.48. Shallow fills at depth 1 or more look like
+/// roughly 130 honest attempts and only claim that much work, so the score
+/// can't separate them; deep fabricated hashes are bounded by
+/// `MAX_PLAUSIBLE_LEADING_ZEROS` instead.
+const MIN_PLAUSIBILITY_SCORE: f64 = 0.5;
+
+/// Refuse `(seed, hash)` submissions that would leave a function's sketch
+/// looking cheaply filled.
+///
+/// Applies them to a copy of `current` and rejects if the result scores
+/// below [`MIN_PLAUSIBILITY_SCORE`] and below `current`, so a sketch that
+/// already scores low can still take honest improvements. Nothing re-runs
+/// the test function, so the caller logs the rejection for manual review.
+fn check_plausible_updates(
+    current: &hyperloglog::HyperLogLog,
+    updates: impl IntoIterator<Item = (u64, u64)>,
+) -> Result<(), ApiError> {
+    let mut updated = current.clone();
+    for (seed, hash) in updates {
+        updated.add(seed, hash);
+    }
+
+    let before = current.plausibility_score();
+    let after = updated.plausibility_score();
+    if after < MIN_PLAUSIBILITY_SCORE && after < before {
+        return Err(ApiError::new(
+            422,
+            "implausible_sketch",
+            format!(
+                "Submission would lower the sketch's plausibility score to {:.2} \
+                 (minimum {:.2}); it was rejected and flagged for review",
+                after, MIN_PLAUSIBILITY_SCORE
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Apply a sparse set of register improvements for one function in a file.
+///
+/// Sits between single submissions and full-sketch uploads: a client that
+/// improved many registers sends just those, and they commit together.
+async fn handle_submit_register_updates(
+    mut req: Request,
+    env: Env,
+    worker_ctx: &Context,
+    r2_key: String,
+) -> Result<Response> {
+    let body: RegisterUpdatesRequest = req
+        .json()
+        .await
+        .map_err(|e| Error::RustError(format!("Invalid JSON body: {}", e)))?;
+
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema_once(&db).await?;
+
+    let bits = hll_store::stored_bits(&db, &r2_key, &body.function_name).await?;
+    let updates =
+        match validate_register_updates(&body.updates, bits, max_plausible_leading_zeros(&env)) {
+            Ok(updates) => updates,
+            Err(err) => return to_worker_error(err),
+        };
+
+    let current = hll_store::get_hll_state(&db, &r2_key, &body.function_name).await?;
+    let submitted = updates.iter().map(|update| (update.seed, update.hash));
+    if let Err(err) = check_plausible_updates(&current, submitted) {
+        console_log!(
+            "[WARN] Flagged register batch for review: {}/{}: {}",
+            r2_key,
+            body.function_name,
+            err.message
+        );
+        return to_worker_error(err);
+    }
+
+    let started = js_sys::Date::now();
+    let outcomes =
+        hll_store::submit_register_updates(&db, &r2_key, &body.function_name, &updates).await?;
+    let duration_ms = js_sys::Date::now() - started;
+
+    for outcome in &outcomes {
+        if let Some(line) = improvement_log(&r2_key, &body.function_name, outcome, duration_ms) {
+            console_log!("{}", line);
+        }
+    }
+
+    for (update, outcome) in updates.iter().zip(&outcomes) {
+        notify_new_record(
+            &env,
+            worker_ctx,
+            outcome.new_record,
+            &r2_key,
+            &body.function_name,
+            update.seed,
+            update.hash,
+        );
+    }
+
+    let hll = hll_store::get_hll_state(&db, &r2_key, &body.function_name).await?;
+
+    json_response(
+        200,
+        &RegisterUpdatesResponse {
+            ok: true,
+            improved: outcomes.iter().map(|outcome| outcome.improved).collect(),
+            estimated_tests: hll.count(),
+        },
+    )
+}
+
+/// Structured log line for a submission outcome, if it lowered a register
+///
+/// Only improvements are logged to keep volume proportional to progress.
+fn improvement_log(
+    r2_key: &str,
+    function_name: &str,
+    outcome: &hll_store::SubmitOutcome,
+    duration_ms: f64,
+) -> Option<String> {
+    if !outcome.improved {
+        return None;
+    }
+
+    let entry = SubmissionLog {
+        event: "register_improved",
+        r2_key,
+        function_name,
+        register: outcome.register_idx,
+        old_hash: outcome.previous.map(|value| value.to_string()),
+        new_hash: outcome.current.to_string(),
+        duration_ms,
+    };
+    serde_json::to_string(&entry).ok()
+}
+
+/// Whether a submission can be rejected from the client's register hint alone
+///
+/// The stored register is at most `expected_current`, so a hash that isn't
+/// below it cannot lower the register, and the handler skips the write and
+/// the state read entirely.
+fn hint_rejects(hash: u64, expected_current: u64) -> bool {
+    hash >= expected_current
+}
+
+async fn handle_submit_test_result(
+    mut req: Request,
+    env: Env,
+    worker_ctx: &Context,
+) -> Result<Response> {
+    let body: SubmitHashRequest = req
+        .json()
+        .await
+        .map_err(|e| Error::RustError(format!("Invalid JSON body: {}", e)))?;
+
+    let seed = match parse_u64_string(&body.seed, "seed") {
+        Ok(value) => value,
+        Err(err) => return to_worker_error(err),
+    };
+    let hash = match parse_u64_string(&body.hash, "hash") {
+        Ok(value) => value,
+        Err(err) => return to_worker_error(err),
+    };
+
+    if let Err(err) = check_plausible_depth(hash, max_plausible_leading_zeros(&env)) {
+        console_log!(
+            "[WARN] Rejected implausible hash {} for {}/{}: {}",
+            hash,
+            body.r2_key,
+            body.function_name,
+            err.message
+        );
+        return to_worker_error(err);
+    }
+
+    if let Some(expected_current) = &body.expected_current {
+        let expected_current = match parse_u64_string(expected_current, "expected_current") {
+            Ok(value) => value,
+            Err(err) => return to_worker_error(err),
+        };
+
+        if hint_rejects(hash, expected_current) {
+            return json_response(
+                200,
+                &SubmitHashResponse {
+                    ok: true,
+                    improved: false,
+                    estimated_tests: None,
+                },
+            );
+        }
+    }
+
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+
+    // Ensure schema exists
+    if let Err(e) = hll_store::ensure_schema_once(&db).await {
+        console_log!("[WARN] Failed to ensure D1 schema: {:?}", e);
+    }
+
+    let current = hll_store::get_hll_state(&db, &body.r2_key, &body.function_name).await?;
+    if let Err(err) = check_plausible_updates(&current, [(seed, hash)]) {
+        console_log!(
+            "[WARN] Flagged submission for review: {}/{} seed {} hash {}: {}",
+            body.r2_key,
+            body.function_name,
+            seed,
+            hash,
+            err.message
+        );
+        return to_worker_error(err);
+    }
+
+    // Submit the hash atomically
+    let started = js_sys::Date::now();
+    let outcome =
+        hll_store::submit_hash(&db, &body.r2_key, &body.function_name, seed, hash).await?;
+    let duration_ms = js_sys::Date::now() - started;
+
+    if let Some(line) = improvement_log(&body.r2_key, &body.function_name, &outcome, duration_ms) {
+        console_log!("{}", line);
+    }
+
+    notify_new_record(
+        &env,
+        worker_ctx,
+        outcome.new_record,
+        &body.r2_key,
+        &body.function_name,
+        seed,
+        hash,
+    );
+
+    // Get updated HLL state for the estimate
     let hll = hll_store::get_hll_state(&db, &body.r2_key, &body.function_name).await?;
 
     json_response(
         200,
         &SubmitHashResponse {
             ok: true,
-            improved,
-            estimated_tests: hll.count(),
+            improved: outcome.improved,
+            estimated_tests: Some(hll.count()),
+        },
+    )
+}
+
+async fn handle_ensure_schema(req: Request, env: Env) -> Result<Response> {
+    if let Err(err) = require_admin(&req, &env) {
+        return to_worker_error(err);
+    }
+
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema(&db).await?;
+    hll_store::migrate_legacy_registers(&db).await?;
+    hll_store::backfill_function_records(&db).await?;
+
+    json_response(
+        200,
+        &EnsureSchemaResponse {
+            ok: true,
+            schema_version: hll_store::SCHEMA_VERSION,
+        },
+    )
+}
+
+/// Days without an improved register before a function is compacted, when
+/// `?idle_days=` is not given
+const DEFAULT_COMPACTION_IDLE_DAYS: u32 = 90;
+
+/// Fold the sketches of long-idle functions down to fewer bits.
+///
+/// Meant to be called by an operator or a scheduled job. Takes
+/// `?idle_days=` (default 90) and `?bits=` (default
+/// `hll_store::COMPACTED_HLL_BITS`, below `DEFAULT_HLL_BITS`) and reports
+/// each compacted function with its stored rows and estimate before and after.
+async fn handle_compact(req: Request, env: Env) -> Result<Response> {
+    if let Err(err) = require_admin(&req, &env) {
+        return to_worker_error(err);
+    }
+
+    let url = req.url()?;
+    let mut idle_days = DEFAULT_COMPACTION_IDLE_DAYS;
+    let mut bits = hll_store::COMPACTED_HLL_BITS;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "idle_days" => match value.parse::<u32>() {
+                Ok(parsed) if parsed > 0 => idle_days = parsed,
+                _ => {
+                    return error_response(
+                        400,
+                        "invalid_idle_days",
+                        "idle_days must be a positive number of days",
+                    )
+                }
+            },
+            "bits" => match value.parse::<u8>() {
+                Ok(parsed) if (1..DEFAULT_HLL_BITS).contains(&parsed) => bits = parsed,
+                _ => {
+                    return error_response(
+                        400,
+                        "invalid_bits",
+                        format!("bits must be between 1 and {}", DEFAULT_HLL_BITS - 1),
+                    )
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let db = match hll_db(&env) {
+        Ok(db) => db,
+        Err(err) => return to_worker_error(err),
+    };
+    hll_store::ensure_schema(&db).await?;
+    let compacted = hll_store::compact_inactive_functions(&db, idle_days, bits).await?;
+
+    json_response(
+        200,
+        &CompactResponse {
+            ok: true,
+            idle_days,
+            bits,
+            compacted: compacted
+                .into_iter()
+                .map(|function| CompactedFunctionResponse {
+                    r2_key: function.r2_key,
+                    function_name: function.function_name,
+                    rows_before: function.rows_before,
+                    rows_after: function.rows_after,
+                    estimate_before: function.estimate_before,
+                    estimate_after: function.estimate_after,
+                })
+                .collect(),
         },
     )
 }
@@ -1063,7 +2252,10 @@ async fn handle_ci_upload(mut req: Request, env: Env) -> Result<Response> {
     }
 
     // Check replay protection
-    let kv = env.kv("CATALOG")?;
+    let kv = match catalog_kv(&env) {
+        Ok(kv) => kv,
+        Err(err) => return to_worker_error(err),
+    };
     let jti_hash = hex::encode(Sha256::digest(claims.jti.as_bytes()));
     match catalog::check_and_mark_replay(&kv, &jti_hash).await {
         Ok(true) => {
@@ -1136,6 +2328,28 @@ async fn handle_ci_upload(mut req: Request, env: Env) -> Result<Response> {
         Err(err) => return to_worker_error(err),
     };
 
+    let aliases = match parse_function_aliases(form.get_field("aliases")) {
+        Ok(aliases) => aliases,
+        Err(err) => return to_worker_error(err),
+    };
+
+    // Resolve aliases against the version this upload replaces as latest
+    let aliases = if aliases.is_empty() {
+        Vec::new()
+    } else {
+        let previous_version = match catalog::get_repo(&kv, &claims.repository).await? {
+            Some(RepoMetadata {
+                latest_version: Some(latest),
+                ..
+            }) => catalog::get_version(&kv, &claims.repository, &latest).await?,
+            _ => None,
+        };
+        match resolve_function_aliases(&aliases, previous_version.as_ref(), &function_names) {
+            Ok(aliases) => aliases,
+            Err(err) => return to_worker_error(err),
+        }
+    };
+
     let mut persisted = false;
     let mut r2_key = None;
 
@@ -1162,7 +2376,10 @@ async fn handle_ci_upload(mut req: Request, env: Env) -> Result<Response> {
         r2_key = Some(storage_key.clone());
 
         // Update KV catalog
-        let kv = env.kv("CATALOG")?;
+        let kv = match catalog_kv(&env) {
+            Ok(kv) => kv,
+            Err(err) => return to_worker_error(err),
+        };
 
         // Ensure repo is in the list
         catalog::ensure_repo_in_list(&kv, &claims.repository).await?;
@@ -1204,12 +2421,26 @@ async fn handle_ci_upload(mut req: Request, env: Env) -> Result<Response> {
         catalog::put_version(&kv, &claims.repository, &version_meta).await?;
 
         // Initialize HLL registers in D1
-        let db = env.d1("HLL_DB")?;
+        let db = match hll_db(&env) {
+            Ok(db) => db,
+            Err(err) => return to_worker_error(err),
+        };
         hll_store::ensure_schema(&db).await?;
 
         for function_name in &function_names {
             hll_store::init_function_registers(&db, &storage_key, function_name).await?;
         }
+
+        for alias in &aliases {
+            hll_store::migrate_function_history(
+                &db,
+                &alias.from_r2_key,
+                &alias.from_function,
+                &storage_key,
+                &alias.to_function,
+            )
+            .await?;
+        }
     }
 
     let payload = CiUploadResponse {
@@ -1231,6 +2462,7 @@ async fn handle_ci_upload(mut req: Request, env: Env) -> Result<Response> {
         function_count: function_names.len(),
         function_names,
         r2_key,
+        aliases,
     };
 
     json_response(200, &payload)
@@ -1257,10 +2489,12 @@ where
 }
 
 #[event(fetch)]
-async fn fetch(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+async fn fetch(req: Request, env: Env, worker_ctx: worker::Context) -> Result<Response> {
     console_error_panic_hook::set_once();
 
-    let router = Router::new();
+    // Route handlers reach the worker context through `ctx.data` to queue
+    // background work such as webhooks
+    let router = Router::with_data(worker_ctx);
 
     router
         .get_async("/api/repositories", |_req, ctx| async move {
@@ -1326,12 +2560,25 @@ async fn fetch(req: Request, env: Env, _ctx: worker::Context) -> Result<Response
                 }
             },
         )
-        .get_async("/api/wasm/*r2_key", |_req, ctx| async move {
+        .get_async("/api/wasm/*r2_key", |req, ctx| async move {
             let r2_key = ctx
                 .param("r2_key")
                 .map(|value| value.to_string())
                 .unwrap_or_default();
-            handle_get_wasm_file(ctx.env, r2_key).await
+            handle_get_wasm_file(req, ctx.env, r2_key).await
+        })
+        .head_async("/api/wasm/*r2_key", |_req, ctx| async move {
+            let r2_key = ctx
+                .param("r2_key")
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            handle_head_wasm_file(ctx.env, r2_key).await
+        })
+        .options("/api/wasm/*r2_key", |_req, _ctx| {
+            Ok(ResponseBuilder::new()
+                .with_header("Allow", "GET, HEAD, OPTIONS")?
+                .with_status(204)
+                .empty())
         })
         .get_async("/api/wasm-hll/*r2_key", |_req, ctx| async move {
             let r2_key = ctx
@@ -1340,8 +2587,42 @@ async fn fetch(req: Request, env: Env, _ctx: worker::Context) -> Result<Response
                 .unwrap_or_default();
             handle_get_wasm_file_hll_state(ctx.env, r2_key).await
         })
+        .post_async("/api/wasm-hll/*r2_key", |req, ctx| async move {
+            let r2_key = ctx
+                .param("r2_key")
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            match handle_submit_register_updates(req, ctx.env, &ctx.data, r2_key).await {
+                Ok(response) => Ok(response),
+                Err(err) => {
+                    console_log!("[ERROR] POST /api/wasm-hll failed: {}", err);
+                    error_response(
+                        500,
+                        "internal_error",
+                        format!("Failed applying register updates: {}", err),
+                    )
+                }
+            }
+        })
+        .get_async("/api/wasm-verify/*r2_key", |req, ctx| async move {
+            let r2_key = ctx
+                .param("r2_key")
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            match handle_verify_function(req, ctx.env, r2_key).await {
+                Ok(response) => Ok(response),
+                Err(err) => {
+                    console_log!("[ERROR] GET /api/wasm-verify failed: {}", err);
+                    error_response(
+                        500,
+                        "internal_error",
+                        format!("Failed verifying stored proofs: {}", err),
+                    )
+                }
+            }
+        })
         .post_async("/api/test-results", |req, ctx| async move {
-            match handle_submit_test_result(req, ctx.env).await {
+            match handle_submit_test_result(req, ctx.env, &ctx.data).await {
                 Ok(response) => Ok(response),
                 Err(err) => {
                     console_log!("[ERROR] POST /api/test-results failed: {}", err);
@@ -1353,6 +2634,32 @@ async fn fetch(req: Request, env: Env, _ctx: worker::Context) -> Result<Response
                 }
             }
         })
+        .post_async("/api/admin/ensure-schema", |req, ctx| async move {
+            match handle_ensure_schema(req, ctx.env).await {
+                Ok(response) => Ok(response),
+                Err(err) => {
+                    console_log!("[ERROR] POST /api/admin/ensure-schema failed: {}", err);
+                    error_response(
+                        500,
+                        "internal_error",
+                        format!("Failed ensuring schema: {}", err),
+                    )
+                }
+            }
+        })
+        .post_async("/api/admin/compact", |req, ctx| async move {
+            match handle_compact(req, ctx.env).await {
+                Ok(response) => Ok(response),
+                Err(err) => {
+                    console_log!("[ERROR] POST /api/admin/compact failed: {}", err);
+                    error_response(
+                        500,
+                        "internal_error",
+                        format!("Failed compacting sketches: {}", err),
+                    )
+                }
+            }
+        })
         .post_async("/api/ci-upload", |req, ctx| async move {
             handle_ci_upload(req, ctx.env).await
         })
@@ -1362,3 +2669,427 @@ async fn fetch(req: Request, env: Env, _ctx: worker::Context) -> Result<Response
         .run(req, env)
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(files: &[(&str, &[&str])]) -> VersionMetadata {
+        VersionMetadata {
+            version: "v2".to_string(),
+            files: files
+                .iter()
+                .map(|(r2_key, functions)| FileMetadata {
+                    r2_key: r2_key.to_string(),
+                    sha256: String::new(),
+                    uploaded_at: String::new(),
+                    functions: functions.iter().map(|name| name.to_string()).collect(),
+                })
+                .collect(),
+            created_at: String::new(),
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn aliases(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_improvement_log_only_for_improving_submissions() {
+        let mut outcome = hll_store::SubmitOutcome {
+            improved: true,
+            new_record: false,
+            register_idx: 3,
+            previous: Some(500),
+            current: 200,
+        };
+        assert_eq!(
+            improvement_log("octo__widgets/v1/abc.wasm", "check", &outcome, 1.5).as_deref(),
+            Some(
+                r#"{"event":"register_improved","r2_key":"octo__widgets/v1/abc.wasm","function_name":"check","register":3,"old_hash":"500","new_hash":"200","duration_ms":1.5}"#
+            )
+        );
+
+        // First write to a register has no old value
+        outcome.previous = None;
+        let line = improvement_log("k", "f", &outcome, 0.0).unwrap();
+        assert!(line.contains(r#""old_hash":null"#), "{}", line);
+
+        outcome.improved = false;
+        assert_eq!(improvement_log("k", "f", &outcome, 0.0), None);
+    }
+
+    #[test]
+    fn test_plausible_depth_rejects_implausibly_deep_hashes() {
+        let max = DEFAULT_MAX_PLAUSIBLE_LEADING_ZEROS;
+        let at_limit = 1u64 << (63 - max);
+        assert!(check_plausible_depth(at_limit, max).is_ok());
+        assert!(check_plausible_depth(u64::MAX, max).is_ok());
+
+        for hash in [at_limit >> 1, 1, 0] {
+            let err = check_plausible_depth(hash, max).unwrap_err();
+            assert_eq!(
+                (err.status, err.code),
+                (422, "implausible_hash"),
+                "{}",
+                hash
+            );
+        }
+        // A limit of 64 accepts every hash, including 0
+        assert!(check_plausible_depth(0, 64).is_ok());
+    }
+
+    #[test]
+    fn test_missing_bindings_report_service_unavailable() {
+        let err = database_not_configured();
+        assert_eq!((err.status, err.code), (503, "database_not_configured"));
+        assert!(err.message.contains("HLL_DB"), "{}", err.message);
+
+        let err = catalog_not_configured();
+        assert_eq!((err.status, err.code), (503, "catalog_not_configured"));
+        assert!(err.message.contains("CATALOG"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_verify_sample_parsing() {
+        assert_eq!(parse_verify_sample("1").unwrap(), 1.0);
+        assert_eq!(parse_verify_sample("0.25").unwrap(), 0.25);
+        for invalid in ["0", "-0.5", "1.5", "NaN", "half", ""] {
+            let err = parse_verify_sample(invalid).unwrap_err();
+            assert_eq!(
+                (err.status, err.code),
+                (400, "invalid_sample"),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_sketch_flags_tampered_and_trapping_registers() {
+        let mut hll = hyperloglog::HyperLogLog::new(DEFAULT_HLL_BITS);
+        for seed in 0..1000u64 {
+            hll.add(seed, splitmix(seed));
+        }
+        let honest = verify_sketch(&hll, 1.0, |seed| Some(splitmix(seed)));
+        assert!(honest.failed_registers.is_empty());
+        assert_eq!(honest.passed, honest.checked);
+
+        // Claim a far lower hash for one register than its seed produces
+        let mut tampered = hll.clone();
+        let register = 7;
+        tampered.add(12_345, register as u64);
+        assert_eq!(tampered.seed(register), 12_345);
+        let report = verify_sketch(&tampered, 1.0, |seed| Some(splitmix(seed)));
+        assert_eq!(report.failed_registers, vec![register]);
+        assert_eq!(report.passed, report.checked - 1);
+
+        // A call that traps or runs out of fuel fails only its own register
+        let trapping_seed = hll.seed(3);
+        let report = verify_sketch(&hll, 1.0, |seed| {
+            (seed != trapping_seed).then(|| splitmix(seed))
+        });
+        assert_eq!(report.failed_registers, vec![3]);
+    }
+
+    #[test]
+    fn test_hint_rejects_only_hashes_that_cannot_improve() {
+        assert!(hint_rejects(500, 500));
+        assert!(hint_rejects(501, 500));
+        assert!(!hint_rejects(499, 500));
+        // An empty register hint never rejects a real hash
+        assert!(!hint_rejects(u64::MAX - 1, u64::MAX));
+    }
+
+    #[test]
+    fn test_record_webhook_needs_a_new_record_at_min_depth() {
+        let deep = u64::MAX >> 40;
+        assert_eq!(deep.leading_zeros(), 40);
+
+        // Deep enough but not a new record
+        assert!(
+            record_webhook_payload(false, 32, "octo__widgets/v1/a.wasm", "f", 7, deep).is_none()
+        );
+        // A new record, but too shallow
+        assert!(
+            record_webhook_payload(true, 41, "octo__widgets/v1/a.wasm", "f", 7, deep).is_none()
+        );
+
+        let payload =
+            record_webhook_payload(true, 40, "octo__widgets/v1/a.wasm", "f", 7, deep).unwrap();
+        assert_eq!(payload.event, "new_record");
+        assert_eq!(payload.repository, "octo/widgets");
+        assert_eq!(payload.r2_key, "octo__widgets/v1/a.wasm");
+        assert_eq!(payload.function_name, "f");
+        assert_eq!(payload.seed, "7");
+        assert_eq!(payload.hash, deep.to_string());
+        assert_eq!(payload.depth, 40);
+    }
+
+    #[test]
+    fn test_snapshot_freshness() {
+        assert_eq!(fresh_snapshot_age(100, 100, 10), Some(0));
+        assert_eq!(fresh_snapshot_age(100, 109, 10), Some(9));
+        // Expires once the age reaches the bound
+        assert_eq!(fresh_snapshot_age(100, 110, 10), None);
+        // A snapshot stamped ahead of this isolate's clock counts as new
+        assert_eq!(fresh_snapshot_age(105, 100, 10), Some(0));
+        // A bound of 0 bypasses snapshots, however recent
+        assert_eq!(fresh_snapshot_age(100, 100, 0), None);
+    }
+
+    #[test]
+    fn test_repository_from_r2_key_decodes_owner_separator() {
+        assert_eq!(
+            repository_from_r2_key("octo__widgets/v1/abc.wasm"),
+            "octo/widgets"
+        );
+        assert_eq!(repository_from_r2_key("octo__widgets"), "octo/widgets");
+        assert_eq!(repository_from_r2_key(""), "");
+    }
+
+    #[test]
+    fn test_parse_function_aliases_accepts_missing_and_rejects_non_objects() {
+        assert!(parse_function_aliases(None).unwrap().is_empty());
+        assert!(parse_function_aliases(Some("  ".to_string()))
+            .unwrap()
+            .is_empty());
+
+        let parsed = parse_function_aliases(Some(r#"{"old": "new"}"#.to_string())).unwrap();
+        assert_eq!(parsed, aliases(&[("old", "new")]));
+
+        for invalid in [r#"["old", "new"]"#, r#"{"old": 1}"#, "not json"] {
+            let err = parse_function_aliases(Some(invalid.to_string())).unwrap_err();
+            assert_eq!(
+                (err.status, err.code),
+                (400, "invalid_aliases"),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_function_aliases_finds_latest_source_file() {
+        let previous = version(&[
+            ("repo/v1/a.wasm", &["old", "kept"]),
+            ("repo/v1/b.wasm", &["old"]),
+        ]);
+        let resolved = resolve_function_aliases(
+            &aliases(&[("old", "new")]),
+            Some(&previous),
+            &names(&["new", "kept"]),
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].from_r2_key, "repo/v1/b.wasm");
+        assert_eq!(resolved[0].from_function, "old");
+        assert_eq!(resolved[0].to_function, "new");
+    }
+
+    #[test]
+    fn test_resolve_function_aliases_rejects_invalid_renames() {
+        let previous = version(&[("repo/v1/a.wasm", &["old", "other", "kept"])]);
+        let exports = names(&["new", "kept"]);
+        let rejected = |pairs: &[(&str, &str)], previous: Option<&VersionMetadata>| {
+            resolve_function_aliases(&aliases(pairs), previous, &exports)
+                .err()
+                .map(|err| (err.status, err.code))
+        };
+
+        // Target missing from the new module
+        assert_eq!(
+            rejected(&[("old", "gone")], Some(&previous)),
+            Some((400, "invalid_alias"))
+        );
+        // Source still exported
+        assert_eq!(
+            rejected(&[("kept", "new")], Some(&previous)),
+            Some((400, "invalid_alias"))
+        );
+        // Two sources onto one target
+        assert_eq!(
+            rejected(&[("old", "new"), ("other", "new")], Some(&previous)),
+            Some((400, "invalid_alias"))
+        );
+        // Source unknown to the previous version, or no previous version
+        assert_eq!(
+            rejected(&[("unknown", "new")], Some(&previous)),
+            Some((400, "invalid_alias"))
+        );
+        assert_eq!(
+            rejected(&[("old", "new")], None),
+            Some((400, "invalid_alias"))
+        );
+    }
+
+    fn entry(register: usize, hash: u64) -> RegisterUpdateEntry {
+        RegisterUpdateEntry {
+            register,
+            seed: "1".to_string(),
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_register_updates_validate_against_stored_bits() {
+        let valid = validate_register_updates(&[entry(5, (0xF << 5) | 5), entry(2, 0x42)], 5, 64);
+        let updates = valid.unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!((updates[0].register_idx, updates[0].hash), (5, 0x1E5));
+
+        // Register 5 doesn't exist once compacted to 2 bits
+        let err = validate_register_updates(&[entry(5, 0x1E5)], 2, 64).unwrap_err();
+        assert_eq!((err.status, err.code), (400, "invalid_register"));
+
+        // 0x46 belongs to register 6 at 5 bits but register 2 at 2 bits
+        let err = validate_register_updates(&[entry(6, 0x46)], 2, 64).unwrap_err();
+        assert_eq!((err.status, err.code), (400, "invalid_register"));
+        assert!(validate_register_updates(&[entry(2, 0x46)], 2, 64).is_ok());
+
+        let err = validate_register_updates(&[entry(3, 0x42)], 5, 64).unwrap_err();
+        assert_eq!((err.status, err.code), (400, "register_mismatch"));
+
+        let err = validate_register_updates(&[entry(2, 0x42)], 5, 32).unwrap_err();
+        assert_eq!((err.status, err.code), (422, "implausible_hash"));
+    }
+
+    #[test]
+    fn test_register_updates_cap_batch_size() {
+        let full: Vec<RegisterUpdateEntry> = (0..MAX_REGISTER_UPDATES)
+            .map(|register| entry(register, 0xFF00 | register as u64))
+            .collect();
+        assert!(validate_register_updates(&full, DEFAULT_HLL_BITS, 64).is_ok());
+
+        let mut oversized = full;
+        oversized.push(entry(0, 0xFF00));
+        let err = validate_register_updates(&oversized, DEFAULT_HLL_BITS, 64).unwrap_err();
+        assert_eq!((err.status, err.code), (400, "too_many_updates"));
+    }
+
+    fn splitmix(seed: u64) -> u64 {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    #[test]
+    fn test_plausibility_threshold_passes_honest_default_sketches() {
+        // Sizes around the partial-occupancy dip where honest scores are lowest
+        for attempts in [10u64, 40, 80, 120, 160, 1_000] {
+            for run in 0..2_000u64 {
+                let mut honest = hyperloglog::HyperLogLog::new(DEFAULT_HLL_BITS);
+                for seed in 0..attempts {
+                    honest.add(seed, splitmix(seed ^ (run << 32)));
+                }
+                let score = honest.plausibility_score();
+                assert!(
+                    score >= MIN_PLAUSIBILITY_SCORE,
+                    "honest run {} of {} attempts scored {}",
+                    run,
+                    attempts,
+                    score
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_plausible_updates_reject_shallow_bulk_fill() {
+        let mut current = hyperloglog::HyperLogLog::new(DEFAULT_HLL_BITS);
+        for seed in 0..2u64 {
+            current.add(seed, splitmix(seed));
+        }
+
+        // Every register filled with a hash no search was needed for
+        let forged = (0..MAX_REGISTER_UPDATES as u64)
+            .map(|register| (register, 0x8000_0000_0000_0000 | register));
+        let err = check_plausible_updates(&current, forged).unwrap_err();
+        assert_eq!((err.status, err.code), (422, "implausible_sketch"));
+        assert!(err.message.contains("flagged for review"));
+
+        // Sent one at a time, the hash that completes the fill is caught
+        let mut nearly_full = hyperloglog::HyperLogLog::new(DEFAULT_HLL_BITS);
+        for register in 1..MAX_REGISTER_UPDATES as u64 {
+            nearly_full.add_hash(0x8000_0000_0000_0000 | register);
+        }
+        assert!(check_plausible_updates(&nearly_full, [(7, 0x8000_0000_0000_0000)]).is_err());
+
+        // The best of an honest run's hashes, one per register
+        let mut honest = current.clone();
+        for seed in 2..5_000u64 {
+            honest.add(seed, splitmix(seed));
+        }
+        let batch = honest
+            .registers()
+            .filter(|register| !register.is_empty())
+            .map(|register| (register.seed, register.hash));
+        assert!(check_plausible_updates(&current, batch).is_ok());
+    }
+
+    #[test]
+    fn test_conditional_get_matches_etag_before_date() {
+        let etag = "\"abc123\"";
+        let uploaded = 1_700_000_000_500;
+
+        assert!(conditional_get_matches(Some(etag), None, etag, uploaded));
+        assert!(conditional_get_matches(
+            Some("\"other\", W/\"abc123\""),
+            None,
+            etag,
+            uploaded
+        ));
+        assert!(conditional_get_matches(Some("*"), None, etag, uploaded));
+
+        // A mismatched ETag wins over a date that would match
+        assert!(!conditional_get_matches(
+            Some("\"other\""),
+            Some(1_800_000_000_000.0),
+            etag,
+            uploaded
+        ));
+    }
+
+    #[test]
+    fn test_conditional_get_compares_whole_seconds() {
+        let etag = "\"abc123\"";
+        let uploaded = 1_700_000_000_500;
+
+        // Same second as the upload, since Last-Modified drops the millis
+        assert!(conditional_get_matches(
+            None,
+            Some(1_700_000_000_000.0),
+            etag,
+            uploaded
+        ));
+        assert!(conditional_get_matches(
+            None,
+            Some(1_700_000_100_000.0),
+            etag,
+            uploaded
+        ));
+        assert!(!conditional_get_matches(
+            None,
+            Some(1_699_999_999_000.0),
+            etag,
+            uploaded
+        ));
+        assert!(!conditional_get_matches(
+            None,
+            Some(f64::NAN),
+            etag,
+            uploaded
+        ));
+        assert!(!conditional_get_matches(None, None, etag, uploaded));
+    }
+}